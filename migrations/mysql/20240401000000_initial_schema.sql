@@ -0,0 +1,70 @@
+CREATE TABLE teams (
+    id VARCHAR(255) PRIMARY KEY NOT NULL,
+    name VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_update TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+CREATE TABLE auths (
+    id VARCHAR(255) PRIMARY KEY NOT NULL,
+    team VARCHAR(255),
+    email VARCHAR(255) NOT NULL UNIQUE,
+    hash VARCHAR(255) NOT NULL,
+    role VARCHAR(32) NOT NULL DEFAULT 'member',
+    totp_secret VARCHAR(255),
+    totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    display_name VARCHAR(255),
+    scroll_speed SMALLINT NOT NULL DEFAULT 3,
+    font_size SMALLINT NOT NULL DEFAULT 48,
+    mirror_mode VARCHAR(16) NOT NULL DEFAULT 'none',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_update TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    FOREIGN KEY (team) REFERENCES teams(id) ON DELETE CASCADE
+);
+
+CREATE TABLE sessions (
+    id VARCHAR(255) PRIMARY KEY NOT NULL,
+    auth VARCHAR(255) NOT NULL,
+    refresh_token TEXT NOT NULL,
+    device_label VARCHAR(255),
+    user_agent VARCHAR(512),
+    ip_address VARCHAR(45),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (auth) REFERENCES auths(id) ON DELETE CASCADE
+);
+
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(255) PRIMARY KEY NOT NULL,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE api_keys (
+    id VARCHAR(255) PRIMARY KEY NOT NULL,
+    team VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    hash VARCHAR(255) NOT NULL,
+    scopes VARCHAR(255) NOT NULL,
+    created_by VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used TIMESTAMP NULL,
+    FOREIGN KEY (team) REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES auths(id) ON DELETE SET NULL
+);
+
+CREATE TABLE recovery_codes (
+    id VARCHAR(255) PRIMARY KEY NOT NULL,
+    auth VARCHAR(255) NOT NULL,
+    hash VARCHAR(255) NOT NULL,
+    used_at TIMESTAMP NULL,
+    FOREIGN KEY (auth) REFERENCES auths(id) ON DELETE CASCADE
+);
+
+CREATE TABLE oidc_identities (
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    auth VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (issuer, subject),
+    FOREIGN KEY (auth) REFERENCES auths(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Broadcasts that were live at shutdown, restored on the next start.
+CREATE TABLE broadcasts (
+    id VARCHAR(255) PRIMARY KEY NOT NULL,
+    owner VARCHAR(255),
+    team VARCHAR(255),
+    saved_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,7 @@
+-- Broadcasts are now saved as they change, along with where the show is up to.
+ALTER TABLE broadcasts
+    ADD COLUMN speed SMALLINT,
+    ADD COLUMN scrolling BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN position INT,
+    ADD COLUMN timer VARCHAR(32),
+    ADD COLUMN updated_at TIMESTAMP NULL;
@@ -0,0 +1,4 @@
+-- Which instance a broadcast's controller is connected to, until the lease runs out.
+ALTER TABLE broadcasts
+    ADD COLUMN controller_instance VARCHAR(255),
+    ADD COLUMN lease_expires_at TIMESTAMP NULL;
@@ -0,0 +1,80 @@
+CREATE TABLE teams (
+    id UUID PRIMARY KEY NOT NULL,
+    name VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_update TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE auths (
+    id UUID PRIMARY KEY NOT NULL,
+    team UUID REFERENCES teams(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    hash VARCHAR(255) NOT NULL,
+    role VARCHAR(32) NOT NULL DEFAULT 'member',
+    totp_secret VARCHAR(255),
+    totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    display_name VARCHAR(255),
+    scroll_speed SMALLINT NOT NULL DEFAULT 3,
+    font_size SMALLINT NOT NULL DEFAULT 48,
+    mirror_mode VARCHAR(16) NOT NULL DEFAULT 'none',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_update TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Emails are matched case-insensitively, as they are under MySQL's default collation
+CREATE UNIQUE INDEX auths_email ON auths (LOWER(email));
+
+CREATE FUNCTION touch_last_update() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.last_update = now();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER teams_last_update BEFORE UPDATE ON teams
+    FOR EACH ROW EXECUTE FUNCTION touch_last_update();
+
+CREATE TRIGGER auths_last_update BEFORE UPDATE ON auths
+    FOR EACH ROW EXECUTE FUNCTION touch_last_update();
+
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY NOT NULL,
+    auth UUID NOT NULL REFERENCES auths(id) ON DELETE CASCADE,
+    refresh_token TEXT NOT NULL,
+    device_label VARCHAR(255),
+    user_agent VARCHAR(512),
+    ip_address VARCHAR(45),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(255) PRIMARY KEY NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY NOT NULL,
+    team UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    hash VARCHAR(255) NOT NULL,
+    scopes VARCHAR(255) NOT NULL,
+    created_by UUID REFERENCES auths(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used TIMESTAMPTZ
+);
+
+CREATE TABLE recovery_codes (
+    id UUID PRIMARY KEY NOT NULL,
+    auth UUID NOT NULL REFERENCES auths(id) ON DELETE CASCADE,
+    hash VARCHAR(255) NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE TABLE oidc_identities (
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    auth UUID NOT NULL REFERENCES auths(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (issuer, subject)
+);
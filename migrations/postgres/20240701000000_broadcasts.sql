@@ -0,0 +1,7 @@
+-- Broadcasts that were live at shutdown, restored on the next start.
+CREATE TABLE broadcasts (
+    id UUID PRIMARY KEY NOT NULL,
+    owner UUID,
+    team UUID,
+    saved_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+-- The script a broadcast has loaded, as its controller names it.
+ALTER TABLE broadcasts ADD COLUMN script VARCHAR(255);
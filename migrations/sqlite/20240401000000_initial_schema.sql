@@ -0,0 +1,76 @@
+CREATE TABLE teams (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_update TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE auths (
+    id TEXT PRIMARY KEY NOT NULL,
+    team TEXT,
+    email TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    hash TEXT NOT NULL,
+    role TEXT NOT NULL DEFAULT 'member',
+    totp_secret TEXT,
+    totp_enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    display_name TEXT,
+    scroll_speed SMALLINT NOT NULL DEFAULT 3,
+    font_size SMALLINT NOT NULL DEFAULT 48,
+    mirror_mode TEXT NOT NULL DEFAULT 'none',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_update TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (team) REFERENCES teams(id) ON DELETE CASCADE
+);
+
+-- SQLite has no ON UPDATE clause, so keep last_update current with a trigger
+CREATE TRIGGER auths_last_update AFTER UPDATE ON auths
+BEGIN
+    UPDATE auths SET last_update = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    auth TEXT NOT NULL,
+    refresh_token TEXT NOT NULL,
+    device_label TEXT,
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (auth) REFERENCES auths(id) ON DELETE CASCADE
+);
+
+CREATE TABLE revoked_tokens (
+    jti TEXT PRIMARY KEY NOT NULL,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE api_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    team TEXT NOT NULL,
+    name TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    created_by TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used TIMESTAMP NULL,
+    FOREIGN KEY (team) REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES auths(id) ON DELETE SET NULL
+);
+
+CREATE TABLE recovery_codes (
+    id TEXT PRIMARY KEY NOT NULL,
+    auth TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    used_at TIMESTAMP NULL,
+    FOREIGN KEY (auth) REFERENCES auths(id) ON DELETE CASCADE
+);
+
+CREATE TABLE oidc_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    auth TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (issuer, subject),
+    FOREIGN KEY (auth) REFERENCES auths(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Broadcasts that were live at shutdown, restored on the next start.
+CREATE TABLE broadcasts (
+    id TEXT PRIMARY KEY NOT NULL,
+    owner TEXT,
+    team TEXT,
+    saved_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
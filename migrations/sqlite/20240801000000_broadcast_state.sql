@@ -0,0 +1,6 @@
+-- Broadcasts are now saved as they change, along with where the show is up to.
+ALTER TABLE broadcasts ADD COLUMN speed INTEGER;
+ALTER TABLE broadcasts ADD COLUMN scrolling BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE broadcasts ADD COLUMN position INTEGER;
+ALTER TABLE broadcasts ADD COLUMN timer TEXT;
+ALTER TABLE broadcasts ADD COLUMN updated_at TIMESTAMP;
@@ -0,0 +1,2 @@
+-- Accounts an operator has disabled can't sign in or use tokens already issued.
+ALTER TABLE auths ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Which instance a broadcast's controller is connected to, until the lease runs out.
+ALTER TABLE broadcasts ADD COLUMN controller_instance TEXT;
+ALTER TABLE broadcasts ADD COLUMN lease_expires_at TIMESTAMP;
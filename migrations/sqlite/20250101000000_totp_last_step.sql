@@ -0,0 +1,2 @@
+-- The time step of the last TOTP code accepted, so a code only works once.
+ALTER TABLE auths ADD COLUMN totp_last_step INTEGER;
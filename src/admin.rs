@@ -0,0 +1,179 @@
+//! Operator tasks for the `livescript-admin` binary, run straight against the database so they
+//! work whether or not a server is up.
+
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, Write},
+};
+
+use uuid::Uuid;
+
+use crate::{
+    types::{DbController, Export, JwtManager, Metrics},
+    Config, Role,
+};
+
+const USAGE: &str = "Usage: livescript-admin <command>
+
+Commands:
+  migrate                          Apply pending database migrations
+  baseline                         Adopt a MySQL database built by hand from the old schemas.sql,
+                                   so migrate can take over
+  users                            List every account
+  teams                            List every team
+  create-team <name>               Create a team and print its id
+  create-user <email> [--team <id>] [--role member|admin|super_admin]
+                                   Create an account; the password is read from stdin
+  reset-password <id or email>     Set a new password read from stdin and sign out everywhere
+  revoke-sessions <id or email>    Sign an account out everywhere
+  export [file]                    Write teams and accounts as JSON, to stdout by default
+  import <file>                    Add the teams and accounts from an export that aren't here yet";
+
+type AdminResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Runs one command. Passwords are read a line at a time from `input`, so they stay out of
+/// the shell history; results are written to `output`.
+pub async fn run(
+    config: &Config,
+    args: &[String],
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> AdminResult {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let Some((&command, args)) = args.split_first() else {
+        return Err(USAGE.into());
+    };
+
+    let jwt = JwtManager::new(config.tokens.clone());
+    let db = DbController::init(&config.database_url, jwt, Metrics::new()).await?;
+
+    match (command, args) {
+        ("migrate", []) => {
+            db.migrate().await?;
+            writeln!(output, "Database is up to date")?;
+        }
+        ("baseline", []) => {
+            db.baseline().await?;
+            writeln!(
+                output,
+                "Initial migration recorded as applied; run migrate for the rest"
+            )?;
+        }
+        ("users", []) => {
+            for user in db.users().await? {
+                let team = user
+                    .team
+                    .map(|team| team.id.to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                writeln!(
+                    output,
+                    "{}\t{}\t{}\t{team}",
+                    user.id,
+                    user.email,
+                    user.role.as_str()
+                )?;
+            }
+        }
+        ("teams", []) => {
+            for team in db.teams().await? {
+                writeln!(output, "{}\t{}", team.id, team.name.unwrap_or_default())?;
+            }
+        }
+        ("create-team", [name]) => {
+            let team = db.create_team(name).await?;
+            writeln!(output, "{}", team.id)?;
+        }
+        ("create-user", [email, options @ ..]) => {
+            let (team, role) = user_options(options)?;
+            let password = read_password(&mut input)?;
+            let user = db.create_user(email, &password, team, role).await?;
+            writeln!(output, "{}", user.id)?;
+        }
+        ("reset-password", [user]) => {
+            let user = db.find_user(user).await?;
+            let password = read_password(&mut input)?;
+            db.reset_password(&user.id.to_string(), &password).await?;
+            writeln!(output, "Password reset for {}", user.email)?;
+        }
+        ("revoke-sessions", [user]) => {
+            let user = db.find_user(user).await?;
+            db.revoke_all_sessions(&user.id.to_string()).await?;
+            writeln!(output, "Signed {} out everywhere", user.email)?;
+        }
+        ("export", []) => {
+            serde_json::to_writer_pretty(&mut output, &db.export().await?)?;
+            writeln!(output)?;
+        }
+        ("export", [path]) => {
+            let export = db.export().await?;
+            let file = create_private(path)?;
+            serde_json::to_writer_pretty(file, &export)?;
+            writeln!(
+                output,
+                "Exported {} teams and {} accounts to {path}",
+                export.teams.len(),
+                export.accounts.len()
+            )?;
+        }
+        ("import", [path]) => {
+            let export: Export = serde_json::from_reader(File::open(path)?)?;
+            let summary = db.import(export).await?;
+            writeln!(
+                output,
+                "Imported {} teams and {} accounts",
+                summary.teams, summary.accounts
+            )?;
+            for skipped in summary.skipped {
+                writeln!(output, "Skipped {skipped}: already exists")?;
+            }
+        }
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn user_options(options: &[&str]) -> Result<(Option<Uuid>, Role), Box<dyn Error + Send + Sync>> {
+    let mut team = None;
+    let mut role = Role::Member;
+
+    for pair in options.chunks(2) {
+        match pair {
+            ["--team", id] => team = Some(Uuid::parse_str(id)?),
+            ["--role", "member"] => role = Role::Member,
+            ["--role", "admin"] => role = Role::Admin,
+            ["--role", "super_admin"] => role = Role::SuperAdmin,
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    Ok((team, role))
+}
+
+/// Prompts on stderr, so whatever the command prints can still be piped on.
+fn read_password(input: &mut impl BufRead) -> Result<String, Box<dyn Error + Send + Sync>> {
+    eprint!("Password: ");
+
+    let mut password = String::new();
+    input.read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("No password given".into());
+    }
+    Ok(password)
+}
+
+/// Exports hold password hashes and two-factor secrets, so on Unix only the owner may read them.
+fn create_private(path: &str) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+
+    // The mode only applies to new files, so tighten one being overwritten as well
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    Ok(file)
+}
@@ -0,0 +1,17 @@
+use std::io;
+
+use livescript::{admin, Config};
+
+#[tokio::main]
+async fn main() {
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("Invalid configuration:\n{err}");
+        std::process::exit(1);
+    });
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(err) = admin::run(&config, &args, io::stdin().lock(), io::stdout()).await {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,90 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use uuid::Uuid;
+
+use super::extractors::{Json, Path, SuperAdmin};
+use crate::{
+    types::{AppError, AuthFailuresResponse, AuthResponse, Broadcast, LiveBroadcastsResponse},
+    ApplicationState,
+};
+
+pub async fn list_live_broadcasts(
+    State(state): State<Arc<ApplicationState>>,
+    _: SuperAdmin,
+) -> Result<impl IntoResponse, AppError> {
+    let broadcasts = state.all_broadcasts().await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(LiveBroadcastsResponse::new(broadcasts)),
+    ))
+}
+
+pub async fn end_broadcast(
+    State(state): State<Arc<ApplicationState>>,
+    _: SuperAdmin,
+    Path(broadcast_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    Broadcast::force_end(&state, &broadcast_id).await?;
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
+
+/// Viewers are identified by the address `GET /admin/broadcasts` lists them under. The kick goes
+/// to every instance, so this one only knows it landed if the viewer was connected here.
+pub async fn kick_viewer(
+    State(state): State<Arc<ApplicationState>>,
+    _: SuperAdmin,
+    Path((broadcast_id, viewer)): Path<(Uuid, SocketAddr)>,
+) -> Result<impl IntoResponse, AppError> {
+    if Broadcast::kick(&state, &broadcast_id, &viewer).await? {
+        return Ok((StatusCode::OK, Json(AuthResponse::new(true, None))));
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(AuthResponse::new(
+            true,
+            Some("Not connected to this instance. Sent to the others.".to_string()),
+        )),
+    ))
+}
+
+/// Accounts are named by id or email, as with `livescript-admin`.
+pub async fn disable_account(
+    State(state): State<Arc<ApplicationState>>,
+    SuperAdmin(admin): SuperAdmin,
+    Path(account): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.db.find_user(&account).await?;
+    // Otherwise the last operator could lock everyone out of the admin API
+    if user.id == admin.user.id {
+        return Err(AppError::Validation(
+            "You can't disable your own account".to_string(),
+        ));
+    }
+    state.db.set_disabled(&user.id.to_string(), true).await?;
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
+
+pub async fn enable_account(
+    State(state): State<Arc<ApplicationState>>,
+    _: SuperAdmin,
+    Path(account): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.db.find_user(&account).await?;
+    state.db.set_disabled(&user.id.to_string(), false).await?;
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
+
+pub async fn list_auth_failures(
+    State(state): State<Arc<ApplicationState>>,
+    _: SuperAdmin,
+) -> Result<impl IntoResponse, AppError> {
+    let failures = state.auth_failures.recent().await;
+
+    Ok((StatusCode::OK, Json(AuthFailuresResponse::new(failures))))
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use uuid::Uuid;
+
+use super::extractors::{AuthUser, Json, Path};
+use crate::{
+    types::{ApiKeyRequest, ApiKeyResponse, AppError, AuthResponse, Role, User},
+    ApplicationState,
+};
+
+/// The team whose keys `user` manages. Keys act for the whole team, so members can't.
+fn team_of(user: &User) -> Result<Uuid, AppError> {
+    if user.role < Role::Admin {
+        return Err(AppError::Forbidden(
+            "Only team admins can manage API keys".to_string(),
+        ));
+    }
+    match &user.team {
+        Some(team) => Ok(team.id),
+        None => Err(AppError::Forbidden(
+            "Join a team to manage API keys".to_string(),
+        )),
+    }
+}
+
+pub async fn create_api_key(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+    Json(request): Json<ApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let team = team_of(&user)?;
+
+    if request.name.trim().is_empty() || request.scopes.is_empty() {
+        return Err(AppError::Validation(
+            "Please name the key and give it at least one scope".to_string(),
+        ));
+    }
+
+    // The plaintext key is only ever shown in this response
+    let (api_key, key) = state.db.create_api_key(&team, &user.id, request).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiKeyResponse::created(api_key, key)),
+    ))
+}
+
+pub async fn list_api_keys(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let team = team_of(&user)?;
+    let api_keys = state.db.api_keys(&team).await?;
+
+    Ok((StatusCode::OK, Json(ApiKeyResponse::list(api_keys))))
+}
+
+pub async fn revoke_api_key(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+    Path(key_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let team = team_of(&user)?;
+    state.db.revoke_api_key(&team, &key_id).await?;
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
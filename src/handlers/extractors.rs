@@ -0,0 +1,295 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{
+        self,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tower_cookies::Cookies;
+use tracing::{field, Span};
+use uuid::Uuid;
+
+use super::http::{CSRF_COOKIE, CSRF_HEADER};
+
+use crate::{
+    types::{
+        AccessTokenClaims, ApiKeyPrincipal, ApiKeyScope, AppError, Role, User, API_KEY_PREFIX,
+    },
+    ApplicationState,
+};
+
+/// The authenticated caller of a request, identified by an access token sent either as an
+/// `Authorization: Bearer` header or in the `lat` cookie.
+#[derive(Debug)]
+pub struct AuthUser {
+    pub user: User,
+    pub claims: AccessTokenClaims,
+}
+
+/// Reads the access token from the `Authorization` header, falling back to the `lat` cookie.
+async fn access_token(
+    parts: &mut Parts,
+    state: &Arc<ApplicationState>,
+) -> Result<String, AppError> {
+    if let Some(header) = parts.headers.get(AUTHORIZATION) {
+        let Some(token) = header
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return Err(AppError::InvalidToken);
+        };
+        return Ok(token.trim().to_string());
+    }
+
+    let cookies = Cookies::from_request_parts(parts, state)
+        .await
+        .map_err(|(_, message)| AppError::internal(message))?;
+
+    let Some(cookie) = cookies.get("lat") else {
+        return Err(AppError::MissingToken);
+    };
+
+    // Browsers attach cookies to cross-site requests too, so changes need proof of our own page
+    if !parts.method.is_safe() {
+        verify_csrf(&parts.headers, &cookies)?;
+    }
+
+    Ok(cookie.value().to_string())
+}
+
+/// Double-submit check: the header has to repeat the CSRF cookie. Pages on the API's origin
+/// read the cookie; pages on an allowed origin get the token from the login or refresh
+/// response. Other sites can do neither.
+fn verify_csrf(headers: &HeaderMap, cookies: &Cookies) -> Result<(), AppError> {
+    let expected = cookies.get(CSRF_COOKIE).ok_or(AppError::InvalidCsrfToken)?;
+    let presented = headers
+        .get(CSRF_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .ok_or(AppError::InvalidCsrfToken)?;
+
+    if Sha256::digest(presented.trim()) != Sha256::digest(expected.value()) {
+        return Err(AppError::InvalidCsrfToken);
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<ApplicationState>> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<ApplicationState>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = access_token(parts, state).await?;
+
+        let Ok(claims) = state.jwt.decode_access_token(&token) else {
+            return Err(AppError::InvalidToken);
+        };
+
+        if state.revoked_tokens.is_revoked(&claims.jti).await {
+            return Err(AppError::RevokedToken);
+        }
+
+        let user = match state.db.user(&claims.sub).await? {
+            Some(user) if user.disabled => return Err(AppError::AccountDisabled),
+            Some(user) => user,
+            // The account was deleted after the token was issued
+            None => return Err(AppError::InvalidToken),
+        };
+
+        // Revoking a session, or changing or resetting the password, ends its access tokens too
+        if !state.db.session_exists(&claims.sub, &claims.sid).await? {
+            return Err(AppError::RevokedToken);
+        }
+
+        Span::current().record("user_id", field::display(user.id));
+        Ok(AuthUser { user, claims })
+    }
+}
+
+/// Either a logged in user or a team API key, for endpoints that hardware controllers and
+/// automation scripts can call as well.
+#[derive(Debug)]
+pub enum Principal {
+    User(Box<AuthUser>),
+    ApiKey(ApiKeyPrincipal),
+}
+
+impl Principal {
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            Principal::User(auth) => Some(auth.user.id),
+            Principal::ApiKey(_) => None,
+        }
+    }
+
+    pub fn team(&self) -> Option<Uuid> {
+        match self {
+            Principal::User(auth) => auth.user.team.as_ref().map(|team| team.id),
+            Principal::ApiKey(key) => Some(key.team),
+        }
+    }
+
+    /// Users may do anything their session allows; API keys only what they were scoped to.
+    pub fn require(&self, scope: ApiKeyScope) -> Result<(), AppError> {
+        match self {
+            Principal::User(_) => Ok(()),
+            Principal::ApiKey(key) if key.scopes.contains(&scope) => Ok(()),
+            Principal::ApiKey(_) => Err(AppError::MissingScope),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<ApplicationState>> for Principal {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<ApplicationState>,
+    ) -> Result<Self, Self::Rejection> {
+        let api_key = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| token.starts_with(API_KEY_PREFIX));
+
+        let Some(api_key) = api_key else {
+            let auth = AuthUser::from_request_parts(parts, state).await?;
+            return Ok(Principal::User(Box::new(auth)));
+        };
+
+        match state.db.verify_api_key(api_key).await? {
+            Some(key) => {
+                Span::current().record("team_id", field::display(key.team));
+                Ok(Principal::ApiKey(key))
+            }
+            None => Err(AppError::InvalidToken),
+        }
+    }
+}
+
+/// A logged in platform operator, for the admin API. Only the `super_admin` role qualifies;
+/// team admins manage their own team and nothing more.
+#[derive(Debug)]
+pub struct SuperAdmin(pub AuthUser);
+
+#[async_trait]
+impl FromRequestParts<Arc<ApplicationState>> for SuperAdmin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<ApplicationState>,
+    ) -> Result<Self, Self::Rejection> {
+        let auth = AuthUser::from_request_parts(parts, state).await?;
+        if auth.user.role != Role::SuperAdmin {
+            return Err(AppError::Forbidden(
+                "Only platform operators can do that".to_string(),
+            ));
+        }
+        Ok(SuperAdmin(auth))
+    }
+}
+
+/// Whether cookies set on this response should be `Secure`: always when configured to be or
+/// when serving TLS, otherwise when a trusted proxy says the client connected over HTTPS.
+#[derive(Debug, Clone, Copy)]
+pub struct SecureCookies(pub bool);
+
+#[async_trait]
+impl FromRequestParts<Arc<ApplicationState>> for SecureCookies {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<ApplicationState>,
+    ) -> Result<Self, Self::Rejection> {
+        let config = &state.config;
+        if config.secure_cookies || config.tls.is_some() {
+            return Ok(SecureCookies(true));
+        }
+
+        // Proxies chaining the header append to it, so the first value is the client's
+        let forwarded_https = config.trust_forwarded_proto
+            && parts
+                .headers
+                .get("x-forwarded-proto")
+                .and_then(|header| header.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"));
+
+        Ok(SecureCookies(forwarded_https))
+    }
+}
+
+/// `axum::Json`, reporting a body that can't be read as an [`AppError`] like any other failure.
+#[derive(Debug)]
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    axum::Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::from_request(request, state).await?;
+        Ok(Json(value))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// `axum::extract::Path`, with the same JSON errors as everything else.
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    extract::Path<T>: FromRequestParts<S, Rejection = PathRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let extract::Path(value) = extract::Path::from_request_parts(parts, state).await?;
+        Ok(Path(value))
+    }
+}
+
+/// `axum::extract::Query`, with the same JSON errors as everything else.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    extract::Query<T>: FromRequestParts<S, Rejection = QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let extract::Query(value) = extract::Query::from_request_parts(parts, state).await?;
+        Ok(Query(value))
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use tracing::warn;
+
+use crate::{
+    types::{HealthResponse, ReadinessCheck, ReadinessResponse},
+    ApplicationState,
+};
+
+/// Liveness: answering at all means the process is up.
+pub async fn healthz() -> impl IntoResponse {
+    Json(HealthResponse { status: "ok" })
+}
+
+/// Readiness: whether this instance should be sent traffic. Failures answer 503, so a load
+/// balancer stops routing here while the database is away, the schema is behind or the server
+/// is draining.
+pub async fn readyz(State(state): State<Arc<ApplicationState>>) -> impl IntoResponse {
+    let database = match state.db.ping().await {
+        Ok(()) => ReadinessCheck::passed("database"),
+        Err(err) => {
+            warn!(?err, "Readiness check couldn't reach the database");
+            ReadinessCheck::failed("database", "unreachable")
+        }
+    };
+
+    let migrations = match state.db.pending_migrations().await {
+        Ok(0) => ReadinessCheck::passed("migrations"),
+        Ok(pending) => ReadinessCheck::failed("migrations", format!("{pending} pending")),
+        Err(err) => {
+            warn!(?err, "Readiness check couldn't read the applied migrations");
+            ReadinessCheck::failed("migrations", "unknown")
+        }
+    };
+
+    let draining = if state.is_draining() {
+        ReadinessCheck::failed("draining", "shutting down")
+    } else {
+        ReadinessCheck::passed("draining")
+    };
+
+    let response = ReadinessResponse::new(vec![database, migrations, draining]);
+    let status = if response.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(response))
+}
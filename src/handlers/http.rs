@@ -1,179 +1,329 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use tower_cookies::{cookie::{time::Duration, SameSite}, Cookie, Cookies};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::TypedHeader;
+use rand::{distributions::Alphanumeric, Rng};
+use tower_cookies::{
+    cookie::{time::Duration, SameSite},
+    Cookie, Cookies,
+};
 
-use crate::{types::{Auth, AuthResponse, JwtManager, UserAccessRequest, UserRegistrationRequest}, ApplicationState};
+use super::extractors::{AuthUser, Json, SecureCookies};
+use crate::{
+    types::{
+        AppError, Auth, AuthResponse, Config, LoginOutcome, SessionDevice, TotpChallengeResponse,
+        TotpLoginRequest, UserAccessRequest, UserRegistrationRequest,
+    },
+    ApplicationState,
+};
+
+pub(super) fn session_device(
+    label: Option<String>,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    addr: SocketAddr,
+) -> SessionDevice {
+    SessionDevice {
+        label,
+        user_agent: user_agent.map(|TypedHeader(user_agent)| user_agent.to_string()),
+        ip_address: Some(addr.ip().to_string()),
+    }
+}
+
+fn token_cookie(
+    SecureCookies(secure): SecureCookies,
+    name: &'static str,
+    token: String,
+    lifetime: std::time::Duration,
+) -> Cookie<'static> {
+    Cookie::build((name, token))
+        .http_only(true)
+        .secure(secure)
+        .max_age(Duration::seconds(lifetime.as_secs() as i64))
+        .same_site(SameSite::Strict)
+        .path("/")
+        .build()
+}
+
+/// Readable by pages on the API's own origin. Pages on other allowed origins can't read it, so
+/// the same token is also sent in the `X-CSRF-Token` response header on login and refresh.
+pub(super) const CSRF_COOKIE: &str = "csrf";
+pub(super) const CSRF_HEADER: &str = "x-csrf-token";
+
+fn csrf_cookie(
+    SecureCookies(secure): SecureCookies,
+    lifetime: std::time::Duration,
+) -> Cookie<'static> {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+
+    Cookie::build((CSRF_COOKIE, token))
+        .secure(secure)
+        .max_age(Duration::seconds(lifetime.as_secs() as i64))
+        .same_site(SameSite::Strict)
+        .path("/")
+        .build()
+}
+
+/// The CSRF token as a response header, which CORS exposes to allowed origins.
+fn csrf_header(token: String) -> [(&'static str, String); 1] {
+    [(CSRF_HEADER, token)]
+}
+
+/// Adds the Access and Refresh Tokens to the cookie jar, with a fresh CSRF token to match.
+/// Returns that token for the response header.
+pub(super) fn add_token_cookies(
+    cookies: &Cookies,
+    config: &Config,
+    secure: SecureCookies,
+    access_token: String,
+    refresh_token: String,
+) -> String {
+    cookies.add(token_cookie(
+        secure,
+        "lat",
+        access_token,
+        config.tokens.access_ttl,
+    ));
+    cookies.add(token_cookie(
+        secure,
+        "lrt",
+        refresh_token,
+        config.tokens.refresh_ttl,
+    ));
+    let csrf = csrf_cookie(secure, config.tokens.refresh_ttl);
+    let token = csrf.value().to_string();
+    cookies.add(csrf);
+    token
+}
+
+/// Clears the token cookies and their CSRF token. The path has to match the one they were set
+/// with.
+pub(super) fn remove_token_cookies(cookies: &Cookies) {
+    cookies.remove(Cookie::build(("lat", "")).path("/").build());
+    cookies.remove(Cookie::build(("lrt", "")).path("/").build());
+    cookies.remove(Cookie::build((CSRF_COOKIE, "")).path("/").build());
+}
 
 pub async fn register_user(
     cookies: Cookies,
+    secure: SecureCookies,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<Arc<ApplicationState>>,
     Json(request): Json<UserRegistrationRequest>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, AppError> {
     if !Auth::validate_email(&request.email) || !Auth::validate_password(&request.password) {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(AuthResponse::new(
-                false,
-                Some("Please enter a valid email or password".to_string()),
-            )),
-        );
+        return Err(AppError::Validation(
+            "Please enter a valid email or password".to_string(),
+        ));
     }
 
+    let device = session_device(request.device.clone(), user_agent, addr);
+
     // Retrieve from database
-    match state.db.register(request).await {
-        Ok((access_token, refresh_token)) => {
-            // Add Access and Refresh Tokens to cookie jar
-            let access_cookie = Cookie::build(("lat", access_token))
-                .http_only(true)
-                .secure(false)
-                .max_age(Duration::days(1))
-                .same_site(SameSite::Strict)
-                .build();
-
-            let refresh_cookie = Cookie::build(("lrt", refresh_token))
-                .http_only(true)
-                .secure(false)
-                .max_age(Duration::days(14))
-                .same_site(SameSite::Strict)
-                .build();
-
-            cookies.add(access_cookie);
-            cookies.add(refresh_cookie);
-
-            (
-                StatusCode::CREATED,
-                Json(AuthResponse::new(
-                    true,
-                    Some("Successfully created new user. Welcome!".to_string()),
-                )),
-            )
-        }
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(AuthResponse::new(false, Some(err))),
-        ),
-    }
+    let (access_token, refresh_token) = state.db.register(request, device).await?;
+    let csrf = add_token_cookies(&cookies, &state.config, secure, access_token, refresh_token);
+
+    Ok((
+        StatusCode::CREATED,
+        csrf_header(csrf),
+        Json(AuthResponse::new(
+            true,
+            Some("Successfully created new user. Welcome!".to_string()),
+        )),
+    ))
 }
 
 pub async fn login_user(
     cookies: Cookies,
+    secure: SecureCookies,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<Arc<ApplicationState>>,
     Json(request): Json<UserAccessRequest>,
-) -> impl IntoResponse {
+) -> Result<Response, AppError> {
     if !Auth::validate_email(&request.email) || !Auth::validate_password(&request.password) {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(AuthResponse::new(
-                false,
-                Some("Please enter a valid email or password".to_string()),
-            )),
-        );
+        return Err(AppError::Validation(
+            "Please enter a valid email or password".to_string(),
+        ));
+    }
+
+    let account = request.email.to_lowercase();
+    if let Err(wait) = state.login_throttle.check(addr.ip(), &account).await {
+        let err = AppError::RateLimited(wait);
+        state
+            .auth_failures
+            .record("password", Some(&account), Some(addr.ip()), &err)
+            .await;
+        return Err(err);
+    }
+
+    let device = session_device(request.device.clone(), user_agent, addr);
+
+    let outcome = state.db.login(request, device).await;
+    match &outcome {
+        Ok(_) => {
+            state.metrics.login("password", true);
+            state.login_throttle.record_success(&account).await;
+        }
+        Err(err @ (AppError::InvalidCredentials | AppError::AccountDisabled)) => {
+            state.metrics.login("password", false);
+            state.login_throttle.record_failure(&account).await;
+            state
+                .auth_failures
+                .record("password", Some(&account), Some(addr.ip()), err)
+                .await;
+        }
+        Err(_) => {}
     }
 
-    match state.db.login(request).await {
-        Ok((access_token, refresh_token)) => {
-            // Add Access and Refresh Tokens to cookie jar
-            let access_cookie = Cookie::build(("lat", access_token))
-                .http_only(true)
-                .secure(false)
-                .max_age(Duration::days(1))
-                .same_site(SameSite::Strict)
-                .build();
-
-            let refresh_cookie = Cookie::build(("lrt", refresh_token))
-                .http_only(true)
-                .secure(false)
-                .max_age(Duration::days(14))
-                .same_site(SameSite::Strict)
-                .build();
-
-            cookies.add(access_cookie);
-            cookies.add(refresh_cookie);
-
-            (
+    match outcome? {
+        LoginOutcome::Tokens((access_token, refresh_token)) => {
+            let csrf =
+                add_token_cookies(&cookies, &state.config, secure, access_token, refresh_token);
+
+            Ok((
                 StatusCode::OK,
+                csrf_header(csrf),
                 Json(AuthResponse::new(
                     true,
                     Some("Successful login. Welcome!".to_string()),
                 )),
             )
+                .into_response())
+        }
+        LoginOutcome::TotpRequired(mfa_token) => {
+            Ok((StatusCode::OK, Json(TotpChallengeResponse::new(mfa_token))).into_response())
         }
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(AuthResponse::new(false, Some(err))),
-        ),
     }
 }
 
-pub async fn logout_user(
+pub async fn login_user_totp(
     cookies: Cookies,
+    secure: SecureCookies,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<Arc<ApplicationState>>,
-) -> impl IntoResponse {
-    let Some(access_token) = cookies.get("lat") else {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(AuthResponse::new(
-                false,
-                Some("Oops. Please try again.".to_string()),
-            )),
-        );
+    Json(request): Json<TotpLoginRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let Ok(claims) = state.jwt.decode_mfa_token(&request.mfa_token) else {
+        return Err(AppError::SessionExpired);
     };
+    if state.revoked_tokens.is_revoked(&claims.jti).await {
+        return Err(AppError::SessionExpired);
+    }
 
-    let Ok(claims) = JwtManager::decode_access_token(access_token.value()) else {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(AuthResponse::new(false, Some("Invalid token.".to_string()))),
-        );
-    };
-    match state.db.logout(&claims.sub).await {
-        Ok(_) => {
-            cookies.remove(Cookie::new("lat", ""));
-            (StatusCode::OK, Json(AuthResponse::new(true, None)))
-        }
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(AuthResponse::new(false, Some(err))),
-        ),
+    // Six digit codes are easy to guess, so they share the password lockout under their own key
+    let account = format!("totp:{}", claims.sub);
+    if let Err(wait) = state.login_throttle.check(addr.ip(), &account).await {
+        let err = AppError::RateLimited(wait);
+        state
+            .auth_failures
+            .record("totp", Some(&claims.sub), Some(addr.ip()), &err)
+            .await;
+        return Err(err);
     }
+
+    let device = session_device(request.device.clone(), user_agent, addr);
+
+    let (access_token, refresh_token) = match state
+        .db
+        .login_with_totp(&claims.sub, &request.code, device)
+        .await
+    {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            if let AppError::InvalidTotpCode | AppError::AccountDisabled = err {
+                state.metrics.login("totp", false);
+                state.login_throttle.record_failure(&account).await;
+                state
+                    .auth_failures
+                    .record("totp", Some(&claims.sub), Some(addr.ip()), &err)
+                    .await;
+            }
+            return Err(err);
+        }
+    };
+
+    state.revoke_mfa_token(&claims).await?;
+    state.metrics.login("totp", true);
+    state.login_throttle.record_success(&account).await;
+    let csrf = add_token_cookies(&cookies, &state.config, secure, access_token, refresh_token);
+
+    Ok((
+        StatusCode::OK,
+        csrf_header(csrf),
+        Json(AuthResponse::new(
+            true,
+            Some("Successful login. Welcome!".to_string()),
+        )),
+    ))
+}
+
+pub async fn logout_user(
+    cookies: Cookies,
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { claims, .. }: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    state.db.logout(&claims.sub, &claims.sid).await?;
+
+    // Both tokens died with the session. Revoking the access token as well turns it away
+    // before any database lookup.
+    state.revoke_access_token(&claims).await?;
+
+    remove_token_cookies(&cookies);
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
 }
 
 pub async fn refresh_user(
     cookies: Cookies,
+    secure: SecureCookies,
     State(state): State<Arc<ApplicationState>>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, AppError> {
     let Some(refresh_cookie) = cookies.get("lrt") else {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(AuthResponse::new(false, None)),
-        );
+        return Err(AppError::MissingToken);
     };
 
-    let Ok(claims) = JwtManager::decode_refresh_token(refresh_cookie.value()) else {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(AuthResponse::new(false, Some("Invalid token".to_string()))),
-        );
+    let Ok(claims) = state.jwt.decode_refresh_token(refresh_cookie.value()) else {
+        return Err(AppError::InvalidToken);
     };
 
-    match state.db.refresh(&claims.sub).await {
-        Ok(access_token) => {
-            let access_cookie = Cookie::build(("lat", access_token))
-                .http_only(true)
-                .secure(false)
-                .max_age(Duration::days(1))
-                .same_site(SameSite::Strict)
-                .build();
+    let access_token = state
+        .db
+        .refresh(&claims.sub, &claims.sid, refresh_cookie.value())
+        .await?;
 
-            cookies.add(access_cookie);
+    cookies.add(token_cookie(
+        secure,
+        "lat",
+        access_token,
+        state.config.tokens.access_ttl,
+    ));
 
-            return (StatusCode::NO_CONTENT, Json(AuthResponse::new(true, None)));
-        }
-        Err(err) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(AuthResponse::new(false, Some(err))),
-            )
+    // Sessions from before CSRF tokens were issued pick one up here
+    let csrf = match cookies.get(CSRF_COOKIE) {
+        Some(csrf) => csrf.value().to_string(),
+        None => {
+            let csrf = csrf_cookie(secure, state.config.tokens.refresh_ttl);
+            let token = csrf.value().to_string();
+            cookies.add(csrf);
+            token
         }
-    }
+    };
+
+    // Pages on other origins call this on load to learn the token they must echo, so unlike
+    // other cookie requests it can't demand one. A forged call only renews the caller's own
+    // access token, which the forger never sees.
+    Ok((
+        StatusCode::OK,
+        csrf_header(csrf),
+        Json(AuthResponse::new(true, None)),
+    ))
 }
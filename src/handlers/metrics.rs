@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap,
+    },
+    response::IntoResponse,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{types::AppError, ApplicationState};
+
+/// Serves Prometheus metrics to scrapers holding `METRICS_TOKEN`. The per-broadcast series name
+/// broadcast ids, which are all a viewer needs to join, so without a token there's nothing here.
+pub async fn metrics(
+    headers: HeaderMap,
+    State(state): State<Arc<ApplicationState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Some(token) = &state.config.metrics_token else {
+        return Err(AppError::NotFound("Metrics"));
+    };
+
+    let presented = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AppError::MissingToken)?;
+
+    // Comparing digests keeps the time taken independent of how much of the token matched
+    if Sha256::digest(presented.trim()) != Sha256::digest(token) {
+        return Err(AppError::InvalidToken);
+    }
+
+    let body = state.metrics.render(&*state.live_broadcasts.lock().await);
+    Ok(([(CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
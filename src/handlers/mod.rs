@@ -1,5 +1,26 @@
+mod admin;
+mod api_keys;
+mod extractors;
+mod health;
 mod http;
+mod metrics;
+mod oidc;
+mod profile;
+mod sessions;
+mod totp;
 mod websocket;
 
-pub use http::{login_user, logout_user, refresh_user, register_user};
+pub use admin::{
+    disable_account, enable_account, end_broadcast, kick_viewer, list_auth_failures,
+    list_live_broadcasts,
+};
+pub use api_keys::{create_api_key, list_api_keys, revoke_api_key};
+pub use extractors::{AuthUser, Principal};
+pub use health::{healthz, readyz};
+pub use http::{login_user, login_user_totp, logout_user, refresh_user, register_user};
+pub use metrics::metrics;
+pub use oidc::{oidc_callback, oidc_login};
+pub use profile::{change_email, change_password, delete_account, get_profile, update_profile};
+pub use sessions::{list_sessions, revoke_all_sessions, revoke_session};
+pub use totp::{confirm_totp, enroll_totp};
 pub use websocket::{init_broadcast, subscribe_to_broadcast};
@@ -0,0 +1,149 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::TypedHeader;
+use tower_cookies::{
+    cookie::{time::Duration, SameSite},
+    Cookie, Cookies,
+};
+
+use super::{
+    extractors::{Json, Query, SecureCookies},
+    http::{add_token_cookies, session_device},
+};
+use crate::{
+    types::{
+        AppError, AuthResponse, LoginOutcome, OidcCallbackRequest, OidcClient, OidcLoginRequest,
+        TotpChallengeResponse,
+    },
+    ApplicationState,
+};
+
+fn configured(state: &ApplicationState) -> Result<&OidcClient, AppError> {
+    state
+        .oidc
+        .as_ref()
+        .ok_or(AppError::NotFound("Single sign-on"))
+}
+
+/// Carries the login in progress from `/auth/oidc/login` to the callback.
+const OIDC_COOKIE: &str = "oidc";
+const OIDC_COOKIE_PATH: &str = "/auth/oidc";
+
+/// `Lax`, since the identity provider sends the browser back with a cross-site redirect.
+fn oidc_cookie(SecureCookies(secure): SecureCookies, token: String) -> Cookie<'static> {
+    Cookie::build((OIDC_COOKIE, token))
+        .http_only(true)
+        .secure(secure)
+        .max_age(Duration::minutes(10))
+        .same_site(SameSite::Lax)
+        .path(OIDC_COOKIE_PATH)
+        .build()
+}
+
+async fn failed(state: &ApplicationState, addr: SocketAddr, err: &AppError) {
+    state.metrics.login("oidc", false);
+    state
+        .auth_failures
+        .record("oidc", None, Some(addr.ip()), err)
+        .await;
+}
+
+pub async fn oidc_login(
+    cookies: Cookies,
+    secure: SecureCookies,
+    State(state): State<Arc<ApplicationState>>,
+    Query(request): Query<OidcLoginRequest>,
+) -> Result<Redirect, AppError> {
+    let (url, login) = configured(&state)?
+        .authorization_url(request.device)
+        .await?;
+
+    let token = state
+        .jwt
+        .new_oidc_state_token(login)
+        .map_err(AppError::internal)?;
+    cookies.add(oidc_cookie(secure, token));
+
+    Ok(Redirect::to(&url))
+}
+
+pub async fn oidc_callback(
+    cookies: Cookies,
+    secure: SecureCookies,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<ApplicationState>>,
+    Query(request): Query<OidcCallbackRequest>,
+) -> Result<Response, AppError> {
+    let oidc = configured(&state)?;
+
+    let (Some(code), Some(login_state)) = (request.code, request.state) else {
+        let message = request
+            .error
+            .map(|error| format!("Single sign-on failed: {error}"))
+            .unwrap_or_else(|| "Single sign-on failed".to_string());
+        let err = AppError::Forbidden(message);
+        failed(&state, addr, &err).await;
+        return Err(err);
+    };
+
+    // Only the browser that started the login can finish it, and only once
+    let login = cookies
+        .get(OIDC_COOKIE)
+        .and_then(|cookie| state.jwt.decode_oidc_state_token(cookie.value()).ok())
+        .map(|claims| claims.login);
+    cookies.remove(
+        Cookie::build((OIDC_COOKIE, ""))
+            .path(OIDC_COOKIE_PATH)
+            .build(),
+    );
+    let Some(login) = login.filter(|login| login.state == login_state) else {
+        let err = AppError::SessionExpired;
+        failed(&state, addr, &err).await;
+        return Err(err);
+    };
+
+    let identity = match oidc.exchange(&code, login).await {
+        Ok(identity) => identity,
+        Err(err) => {
+            failed(&state, addr, &err).await;
+            return Err(err);
+        }
+    };
+    let device = session_device(identity.device.clone(), user_agent, addr);
+
+    let outcome = match state.db.oidc_login(identity, device).await {
+        Ok(outcome) => outcome,
+        Err(err @ AppError::AccountDisabled) => {
+            failed(&state, addr, &err).await;
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+    state.metrics.login("oidc", true);
+
+    match outcome {
+        LoginOutcome::Tokens((access_token, refresh_token)) => {
+            // A redirect can't hand the CSRF token to script, so pages pick it up from /auth/refresh
+            add_token_cookies(&cookies, &state.config, secure, access_token, refresh_token);
+
+            Ok((
+                StatusCode::OK,
+                Json(AuthResponse::new(
+                    true,
+                    Some("Successful login. Welcome!".to_string()),
+                )),
+            )
+                .into_response())
+        }
+        // Finished at /auth/login/totp, as after a password
+        LoginOutcome::TotpRequired(mfa_token) => {
+            Ok((StatusCode::OK, Json(TotpChallengeResponse::new(mfa_token))).into_response())
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use tower_cookies::Cookies;
+
+use super::{
+    extractors::{AuthUser, Json},
+    http::remove_token_cookies,
+};
+use crate::{
+    types::{
+        AccountDeletionRequest, AppError, Auth, AuthResponse, EmailChangeRequest,
+        PasswordChangeRequest, ProfileResponse, ProfileUpdateRequest, User,
+    },
+    ApplicationState,
+};
+
+/// Changes that re-check the password are as good for guessing it as a login, so they share
+/// the login throttle and lockout.
+async fn reverified<T>(
+    state: &ApplicationState,
+    user: &User,
+    addr: SocketAddr,
+    change: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    let account = user.email.to_lowercase();
+    if let Err(wait) = state.login_throttle.check(addr.ip(), &account).await {
+        let err = AppError::RateLimited(wait);
+        state
+            .auth_failures
+            .record("reverify", Some(&account), Some(addr.ip()), &err)
+            .await;
+        return Err(err);
+    }
+
+    let outcome = change.await;
+    match &outcome {
+        Ok(_) => state.login_throttle.record_success(&account).await,
+        Err(err @ AppError::IncorrectPassword) => {
+            state.login_throttle.record_failure(&account).await;
+            state
+                .auth_failures
+                .record("reverify", Some(&account), Some(addr.ip()), err)
+                .await;
+        }
+        Err(_) => {}
+    }
+    outcome
+}
+
+pub async fn get_profile(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let profile = state.db.profile(&user.id.to_string()).await?;
+
+    Ok((StatusCode::OK, Json(ProfileResponse::new(profile))))
+}
+
+pub async fn update_profile(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+    Json(request): Json<ProfileUpdateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    request.validate()?;
+
+    let profile = state
+        .db
+        .update_profile(&user.id.to_string(), request)
+        .await?;
+
+    Ok((StatusCode::OK, Json(ProfileResponse::new(profile))))
+}
+
+pub async fn change_email(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+    Json(request): Json<EmailChangeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if !Auth::validate_email(&request.email) {
+        return Err(AppError::Validation(
+            "Please enter a valid email".to_string(),
+        ));
+    }
+
+    let id = user.id.to_string();
+    reverified(&state, &user, addr, state.db.change_email(&id, request)).await?;
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
+
+pub async fn change_password(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, claims }: AuthUser,
+    Json(request): Json<PasswordChangeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if !Auth::validate_password(&request.new_password) {
+        return Err(AppError::Validation(
+            "Please enter a valid password".to_string(),
+        ));
+    }
+
+    let id = user.id.to_string();
+    let change = state.db.change_password(&id, &claims.sid, request);
+    reverified(&state, &user, addr, change).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(AuthResponse::new(
+            true,
+            Some("Password changed. Other devices have been signed out.".to_string()),
+        )),
+    ))
+}
+
+pub async fn delete_account(
+    cookies: Cookies,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, claims }: AuthUser,
+    Json(request): Json<AccountDeletionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let id = user.id.to_string();
+    reverified(&state, &user, addr, state.db.delete_account(&id, request)).await?;
+
+    state.revoke_access_token(&claims).await?;
+    remove_token_cookies(&cookies);
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use tower_cookies::Cookies;
+
+use super::{
+    extractors::{AuthUser, Json, Path},
+    http::remove_token_cookies,
+};
+use crate::{
+    types::{AppError, AuthResponse, SessionsResponse},
+    ApplicationState,
+};
+
+pub async fn list_sessions(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { claims, .. }: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let mut sessions = state.db.sessions(&claims.sub).await?;
+
+    // Flag the session making this request so clients can tell it apart
+    for session in sessions.iter_mut() {
+        session.current = session.id == claims.sid;
+    }
+
+    Ok((StatusCode::OK, Json(SessionsResponse::new(sessions))))
+}
+
+pub async fn revoke_session(
+    cookies: Cookies,
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { claims, .. }: AuthUser,
+    Path(session_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state.db.revoke_session(&claims.sub, &session_id).await?;
+
+    if session_id == claims.sid {
+        state.revoke_access_token(&claims).await?;
+        remove_token_cookies(&cookies);
+    }
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
+
+pub async fn revoke_all_sessions(
+    cookies: Cookies,
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { claims, .. }: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    state.db.revoke_all_sessions(&claims.sub).await?;
+
+    state.revoke_access_token(&claims).await?;
+    remove_token_cookies(&cookies);
+
+    Ok((StatusCode::OK, Json(AuthResponse::new(true, None))))
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+
+use super::extractors::{AuthUser, Json};
+use crate::{
+    types::{AppError, RecoveryCodesResponse, TotpCodeRequest, TotpEnrollmentResponse},
+    ApplicationState,
+};
+
+pub async fn enroll_totp(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let (secret, provisioning_uri) = state.db.begin_totp_enrollment(&user).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(TotpEnrollmentResponse::new(secret, provisioning_uri)),
+    ))
+}
+
+pub async fn confirm_totp(
+    State(state): State<Arc<ApplicationState>>,
+    AuthUser { user, .. }: AuthUser,
+    Json(request): Json<TotpCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let recovery_codes = state
+        .db
+        .confirm_totp(&user.id.to_string(), &request.code)
+        .await?;
+
+    // Recovery codes are only ever shown in this response
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse::new(recovery_codes)),
+    ))
+}
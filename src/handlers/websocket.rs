@@ -1,18 +1,65 @@
 use std::{net::SocketAddr, sync::Arc};
 
-use axum::{extract::{ConnectInfo, State, WebSocketUpgrade}, response::IntoResponse};
+use axum::{
+    extract::{ConnectInfo, State, WebSocketUpgrade},
+    http::{
+        header::{HOST, ORIGIN},
+        HeaderMap,
+    },
+    response::{IntoResponse, Response},
+};
 use axum_extra::TypedHeader;
+use serde::Deserialize;
+use tracing::{field, info, info_span, Instrument};
+use uuid::Uuid;
 
-use crate::{types::Broadcast, ApplicationState};
+use super::extractors::{Principal, Query};
+use crate::{
+    types::{ApiKeyScope, AppError, Broadcast, Config},
+    ApplicationState,
+};
 
-fn log_user_agent(user_agent: Option<TypedHeader<headers::UserAgent>>, addr: SocketAddr) {
+fn log_user_agent(user_agent: Option<TypedHeader<headers::UserAgent>>) {
     let user_agent = if let Some(TypedHeader(user_agent)) = user_agent {
         user_agent.to_string()
     } else {
         String::from("Unknown browser")
     };
 
-    println!("`{user_agent}` at {addr} connected.");
+    info!(%user_agent, "WebSocket connected");
+}
+
+/// CORS doesn't cover WebSocket upgrades, so without this any site could open one with the
+/// user's cookies. Clients that send no `Origin`, like hardware controllers, aren't browsers.
+fn check_origin(headers: &HeaderMap, config: &Config) -> Result<(), AppError> {
+    let Some(origin) = headers.get(ORIGIN) else {
+        return Ok(());
+    };
+    let origin = origin.to_str().unwrap_or_default().trim_end_matches('/');
+
+    let same_origin = headers
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .zip(origin.split_once("://"))
+        .is_some_and(|(host, (_, authority))| authority.eq_ignore_ascii_case(host));
+    let allowed = config
+        .cors_origins
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(origin));
+
+    if same_origin || allowed {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(format!(
+            "Origin {origin} is not allowed"
+        )))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BroadcastInitQuery {
+    /// A broadcast to take back control of, such as one restored after a restart.
+    resume: Option<Uuid>,
 }
 
 pub async fn init_broadcast(
@@ -20,9 +67,34 @@ pub async fn init_broadcast(
     user_agent: Option<TypedHeader<headers::UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<Arc<ApplicationState>>,
-) -> impl IntoResponse {
-    log_user_agent(user_agent, addr);
-    ws.on_upgrade(move |socket| Broadcast::init(socket, addr, state))
+    Query(query): Query<BroadcastInitQuery>,
+    headers: HeaderMap,
+    principal: Principal,
+) -> Result<Response, AppError> {
+    check_origin(&headers, &state.config)?;
+    principal.require(ApiKeyScope::BroadcastControl)?;
+
+    if state.is_draining() {
+        return Err(AppError::ShuttingDown(state.config.drain_period));
+    }
+
+    if let Some(broadcast_id) = &query.resume {
+        Broadcast::verify_resumable(&state, broadcast_id, &principal).await?;
+    }
+
+    log_user_agent(user_agent);
+
+    // The socket outlives the request, so it gets a span of its own
+    let span = info_span!(
+        "broadcast",
+        broadcast_id = query.resume.map(field::display),
+        user_id = principal.user_id().map(field::display),
+        team_id = principal.team().map(field::display),
+        peer = %addr,
+    );
+    Ok(ws.on_upgrade(move |socket| {
+        Broadcast::init(socket, addr, principal, query.resume, state).instrument(span)
+    }))
 }
 
 pub async fn subscribe_to_broadcast(
@@ -30,7 +102,13 @@ pub async fn subscribe_to_broadcast(
     user_agent: Option<TypedHeader<headers::UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<Arc<ApplicationState>>,
-) -> impl IntoResponse {
-    log_user_agent(user_agent, addr);
-    ws.on_upgrade(move |socket| Broadcast::subscribe(socket, addr, state))
-}
\ No newline at end of file
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    check_origin(&headers, &state.config)?;
+    log_user_agent(user_agent);
+
+    let span = info_span!("viewer", broadcast_id = field::Empty, peer = %addr);
+    Ok(ws
+        .on_upgrade(move |socket| Broadcast::subscribe(socket, addr, state).instrument(span))
+        .into_response())
+}
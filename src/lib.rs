@@ -1,11 +1,250 @@
+pub mod admin;
 mod handlers;
 mod types;
 
+use std::{error::Error, future::Future, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderName, HeaderValue, Method,
+    },
+    routing::{delete, get, post, put},
+    Router,
+};
+use tokio::net::TcpListener;
+use tower_cookies::CookieManagerLayer;
+use tower_http::{
+    cors::CorsLayer,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{field, info, info_span, Level};
+use tracing_subscriber::EnvFilter;
+
 pub use handlers::{
-    init_broadcast, login_user, logout_user, refresh_user, register_user, subscribe_to_broadcast,
+    change_email, change_password, confirm_totp, create_api_key, delete_account, disable_account,
+    enable_account, end_broadcast, enroll_totp, get_profile, healthz, init_broadcast, kick_viewer,
+    list_api_keys, list_auth_failures, list_live_broadcasts, list_sessions, login_user,
+    login_user_totp, logout_user, metrics, oidc_callback, oidc_login, readyz, refresh_user,
+    register_user, revoke_all_sessions, revoke_api_key, revoke_session, subscribe_to_broadcast,
+    update_profile, AuthUser, Principal,
+};
+pub use types::{
+    ApiKeyScope, AppError, ApplicationState, Config, ErrorResponse, LogFormat, MirrorMode,
+    OidcConfig, Profile, Role, Team, TlsConfig, TokenConfig, User,
 };
-pub use types::ApplicationState;
+
+/// Logs to stdout, filtered by `RUST_LOG` when it's set. Call once, before anything else logs.
+pub fn init_tracing(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("livescript=info,tower_http=info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
 
 pub fn welcome() {
-    println!("Welcome to the LiveScript API!");
+    info!("Welcome to the LiveScript API!");
+}
+
+/// Connects to the database and builds the LiveScript router, ready to nest under a prefix or
+/// wrap in your own layers. Serve it with `into_make_service_with_connect_info::<SocketAddr>()`,
+/// since the handlers need the client's address.
+pub async fn app(config: Config) -> Result<Router, Box<dyn Error + Send + Sync>> {
+    Ok(router(ApplicationState::init(config).await?))
+}
+
+/// Every route the server answers, bound to existing state.
+pub fn router(state: Arc<ApplicationState>) -> Router {
+    let cors = cors(&state.config.cors_origins);
+
+    let router = Router::new()
+        .route("/auth/register", post(register_user))
+        .route("/auth/login", post(login_user))
+        .route("/auth/login/totp", post(login_user_totp))
+        .route("/auth/oidc/login", get(oidc_login))
+        .route("/auth/oidc/callback", get(oidc_callback))
+        .route("/auth/logout", post(logout_user))
+        .route("/auth/refresh", post(refresh_user))
+        .route(
+            "/auth/sessions",
+            get(list_sessions).delete(revoke_all_sessions),
+        )
+        .route("/auth/sessions/:id", delete(revoke_session))
+        .route("/auth/totp/enroll", post(enroll_totp))
+        .route("/auth/totp/confirm", post(confirm_totp))
+        .route(
+            "/me",
+            get(get_profile)
+                .patch(update_profile)
+                .delete(delete_account),
+        )
+        .route("/me/email", put(change_email))
+        .route("/me/password", put(change_password))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/:id", delete(revoke_api_key))
+        .route("/broadcast/init", get(init_broadcast))
+        .route("/broadcast/subscribe", get(subscribe_to_broadcast))
+        .route("/admin/broadcasts", get(list_live_broadcasts))
+        .route("/admin/broadcasts/:id/end", post(end_broadcast))
+        .route("/admin/broadcasts/:id/viewers/:addr", delete(kick_viewer))
+        .route("/admin/users/:id/disable", post(disable_account))
+        .route("/admin/users/:id/enable", post(enable_account))
+        .route("/admin/auth-failures", get(list_auth_failures))
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state)
+        .layer(CookieManagerLayer::new());
+
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+    .layer(
+        TraceLayer::new_for_http()
+            .make_span_with(request_span)
+            .on_response(DefaultOnResponse::new().level(Level::INFO)),
+    )
+}
+
+/// Lets the configured origins call the API with cookies. Without any, browsers keep to the
+/// same-origin policy.
+fn cors(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let origins = origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect::<Vec<_>>();
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_credentials(true)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                HeaderName::from_static("x-csrf-token"),
+            ])
+            .expose_headers([HeaderName::from_static("x-csrf-token")])
+            .max_age(std::time::Duration::from_secs(60 * 60)),
+    )
+}
+
+/// Every request is traced with who made it. `user_id` is filled in once they're authenticated.
+fn request_span(request: &Request) -> tracing::Span {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| field::display(addr));
+
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        peer,
+        user_id = field::Empty,
+        team_id = field::Empty,
+    )
+}
+
+/// Serves the app on `config.bind_address`, over HTTPS when `config.tls` is set, until `shutdown`
+/// resolves. Live broadcasts are then
+/// told the server is restarting and drained, and whatever is left is saved for the next start.
+pub async fn serve(
+    config: Config,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Bad certificates should stop the server before it connects to anything
+    let rustls = match &config.tls {
+        Some(tls) => Some(types::tls::load(tls).await?),
+        None => None,
+    };
+
+    let listener = TcpListener::bind(config.bind_address).await?;
+    info!(address = %listener.local_addr()?, tls = rustls.is_some(), "Listening");
+    let tls = config.tls.clone();
+    let state = ApplicationState::init(config).await?;
+
+    // Live broadcasts are warned and given the drain period before the server stops
+    let draining = state.clone();
+    let shutdown = async move {
+        shutdown.await;
+        draining.drain().await;
+    };
+    let app = router(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+
+    match (tls, rustls) {
+        (Some(tls), Some(rustls)) => {
+            let watcher = types::tls::watch(tls, rustls.clone());
+            let handle = axum_server::Handle::new();
+            let stopping = handle.clone();
+            tokio::spawn(async move {
+                shutdown.await;
+                stopping.graceful_shutdown(None);
+            });
+
+            let served = axum_server::from_tcp_rustls(listener.into_std()?, rustls)
+                .handle(handle)
+                .serve(app)
+                .await;
+            watcher.abort();
+            served?;
+        }
+        _ => {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await?
+        }
+    }
+
+    state.persist_broadcasts().await?;
+    Ok(())
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM where there is one.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Applies pending database migrations without starting the server.
+pub async fn migrate(config: &Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let jwt = types::JwtManager::new(config.tokens.clone());
+    let db = types::DbController::init(&config.database_url, jwt, types::Metrics::new()).await?;
+    db.migrate().await?;
+    Ok(())
 }
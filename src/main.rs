@@ -1,37 +1,28 @@
-use std::{error::Error, net::SocketAddr};
+use std::error::Error;
 
-use axum::{
-    routing::{get, post},
-    Router,
-};
-use livescript::{self, ApplicationState};
-use tokio::net::TcpListener;
-use tower_cookies::CookieManagerLayer;
+use livescript::{self, Config};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("Invalid configuration:\n{err}");
+        std::process::exit(1);
+    });
+    livescript::init_tracing(config.log_format);
     livescript::welcome();
 
-    let app = Router::new()
-        .route("/auth/register", post(livescript::register_user))
-        .route("/auth/login", post(livescript::login_user))
-        .route("/auth/logout", get(livescript::logout_user))
-        .route("/auth/refresh", get(livescript::refresh_user))
-        .route("/broadcast/init", get(livescript::init_broadcast))
-        .route(
-            "/broadcast/subscribe",
-            get(livescript::subscribe_to_broadcast),
-        )
-        .with_state(ApplicationState::init().await)
-        .layer(CookieManagerLayer::new());
+    match std::env::args().nth(1).as_deref() {
+        Some("migrate") => {
+            livescript::migrate(&config).await?;
+            tracing::info!("Database is up to date");
+            return Ok(());
+        }
+        Some(command) => {
+            eprintln!("Unknown command `{command}`. Usage: livescript [migrate]");
+            std::process::exit(2);
+        }
+        None => {}
+    }
 
-    let listener = TcpListener::bind("127.0.0.1:8000").await?;
-
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await?;
-
-    Ok(())
+    livescript::serve(config, livescript::shutdown_signal()).await
 }
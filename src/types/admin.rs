@@ -0,0 +1,54 @@
+use std::net::SocketAddr;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::AuthFailure;
+
+/// A broadcast live on any instance, as operators see it.
+#[derive(Debug, Serialize)]
+pub struct LiveBroadcast {
+    pub id: Uuid,
+    pub owner: Option<Uuid>,
+    pub team: Option<Uuid>,
+    /// Whether its controller is connected to the instance that answered.
+    pub controlled_here: bool,
+    /// Viewers connected to the instance that answered, the ones it can kick.
+    pub viewers: Vec<SocketAddr>,
+}
+
+/// Each listing says what it can't see, since a load balancer picks the instance that answers.
+#[derive(Debug, Serialize)]
+pub struct LiveBroadcastsResponse {
+    success: bool,
+    message: &'static str,
+    broadcasts: Vec<LiveBroadcast>,
+}
+
+impl LiveBroadcastsResponse {
+    pub fn new(broadcasts: Vec<LiveBroadcast>) -> Self {
+        Self {
+            success: true,
+            message: "Broadcasts are listed from every instance, but controllers and viewers \
+                      only from the one that answered",
+            broadcasts,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthFailuresResponse {
+    success: bool,
+    message: &'static str,
+    failures: Vec<AuthFailure>,
+}
+
+impl AuthFailuresResponse {
+    pub fn new(failures: Vec<AuthFailure>) -> Self {
+        Self {
+            success: true,
+            message: "Only failures seen by the instance that answered",
+            failures,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::error::AppError;
+
+/// Prefix identifying API keys in an `Authorization: Bearer` header.
+pub const API_KEY_PREFIX: &str = "ls_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiKeyScope {
+    #[serde(rename = "broadcast:control")]
+    BroadcastControl,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::BroadcastControl => "broadcast:control",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "broadcast:control" => Some(ApiKeyScope::BroadcastControl),
+            _ => None,
+        }
+    }
+
+    /// Scopes are stored as a comma separated list.
+    pub fn join(scopes: &[ApiKeyScope]) -> String {
+        scopes
+            .iter()
+            .map(ApiKeyScope::as_str)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Scopes no longer offered, like `scripts:read`, are dropped.
+    pub fn split(scopes: &str) -> Vec<ApiKeyScope> {
+        scopes.split(',').filter_map(ApiKeyScope::parse).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// A verified API key presented by a client.
+#[derive(Debug, Clone)]
+pub struct ApiKeyPrincipal {
+    pub id: Uuid,
+    pub team: Uuid,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+/// Secret half of a newly created key. Only its argon2 hash is stored.
+pub struct ApiKeySecret {
+    pub id: Uuid,
+    pub hash: String,
+    pub token: String,
+}
+
+impl ApiKeySecret {
+    pub fn generate() -> Result<Self, AppError> {
+        let id = Uuid::new_v4();
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+
+        let hash = Argon2::default()
+            .hash_password(secret.as_bytes(), &SaltString::generate(&mut OsRng))
+            .map_err(AppError::internal)?
+            .to_string();
+
+        Ok(Self {
+            id,
+            hash,
+            token: format!("{API_KEY_PREFIX}{}_{secret}", id.simple()),
+        })
+    }
+
+    /// Splits a presented token into its key id and secret.
+    pub fn parse(token: &str) -> Option<(Uuid, &str)> {
+        let (id, secret) = token.strip_prefix(API_KEY_PREFIX)?.split_once('_')?;
+        Some((Uuid::parse_str(id).ok()?, secret))
+    }
+
+    pub fn verify(secret: &str, hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(hash) else {
+            return false;
+        };
+        Argon2::default()
+            .verify_password(secret.as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ApiKeyResponse {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<ApiKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_keys: Option<Vec<ApiKey>>,
+}
+
+impl ApiKeyResponse {
+    pub fn created(api_key: ApiKey, key: String) -> Self {
+        Self {
+            success: true,
+            key: Some(key),
+            api_key: Some(api_key),
+            api_keys: None,
+        }
+    }
+
+    pub fn list(api_keys: Vec<ApiKey>) -> Self {
+        Self {
+            success: true,
+            key: None,
+            api_key: None,
+            api_keys: Some(api_keys),
+        }
+    }
+}
@@ -1,23 +1,227 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use tokio::sync::Mutex;
+use chrono::Utc;
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{info, warn};
 use uuid::Uuid;
 
-use super::{Broadcast, DbController};
+use super::{
+    auth_failures::AuthFailureLog,
+    bus,
+    jwt::{AccessTokenClaims, MfaTokenClaims},
+    repository::StoredBroadcast,
+    AppError, Broadcast, BroadcastBus, Channels, Config, DbController, JwtManager, LiveBroadcast,
+    LoginThrottle, Metrics, OidcClient, RevocationList,
+};
+
+/// Saved broadcasts untouched for this long are treated as abandoned rather than restored.
+const ABANDONED_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
 
 #[derive(Debug)]
 pub struct ApplicationState {
+    pub config: Config,
+    pub jwt: JwtManager,
+    /// Broadcasts this instance has controllers or viewers for. The database is the registry
+    /// every instance shares.
     pub live_broadcasts: Mutex<HashMap<Uuid, Broadcast>>,
+    pub channels: Arc<Channels>,
+    pub bus: Box<dyn BroadcastBus>,
     pub db: DbController,
+    pub revoked_tokens: RevocationList,
+    pub login_throttle: LoginThrottle,
+    pub auth_failures: AuthFailureLog,
+    pub oidc: Option<OidcClient>,
+    pub metrics: Metrics,
+    draining: AtomicBool,
 }
 
 impl ApplicationState {
-    pub async fn init() -> Arc<ApplicationState> {
-        Arc::new(ApplicationState {
-            live_broadcasts: Mutex::new(HashMap::new()),
-            db: DbController::init()
+    pub async fn init(
+        config: Config,
+    ) -> Result<Arc<ApplicationState>, Box<dyn Error + Send + Sync>> {
+        let jwt = JwtManager::new(config.tokens.clone());
+        let metrics = Metrics::new();
+        let db = DbController::init(&config.database_url, jwt.clone(), metrics.clone()).await?;
+        if config.auto_migrate {
+            db.migrate().await?;
+        }
+        let revoked_tokens = db.revoked_tokens().await?;
+        let oidc = config.oidc.clone().map(OidcClient::new);
+        let channels = Arc::new(Channels::default());
+        let bus = bus::connect(&config.bus_url, channels.clone()).await?;
+
+        // Broadcasts that were live when the server last stopped wait here for their controllers.
+        // Ones whose controller is connected to another instance are left to it.
+        let live_broadcasts =
+            db.broadcasts(Utc::now() - ABANDONED_AFTER)
+                .await?
+                .into_iter()
+                .filter(|stored| {
+                    stored.lease.as_ref().is_none_or(|lease| {
+                        lease.instance == config.instance_id || !lease.is_live()
+                    })
+                })
+                .map(|stored| (stored.id, Broadcast::restore(stored)))
+                .collect();
+
+        Ok(Arc::new(ApplicationState {
+            config,
+            jwt,
+            live_broadcasts: Mutex::new(live_broadcasts),
+            channels,
+            bus,
+            db,
+            revoked_tokens: RevocationList::new(revoked_tokens),
+            login_throttle: LoginThrottle::default(),
+            auth_failures: AuthFailureLog::default(),
+            oidc,
+            metrics,
+            draining: AtomicBool::new(false),
+        }))
+    }
+
+    /// Viewers currently watching a live broadcast, not counting the controller that started it.
+    pub async fn viewers(&self, broadcast: &Uuid) -> Option<usize> {
+        let broadcasts = self.live_broadcasts.lock().await;
+        broadcasts
+            .get(broadcast)
+            .map(|broadcast| broadcast.subs.len())
+    }
+
+    /// Every broadcast live on any instance, with who is connected to this one.
+    pub async fn all_broadcasts(&self) -> Result<Vec<LiveBroadcast>, AppError> {
+        let stored = self.db.broadcasts(Utc::now() - ABANDONED_AFTER).await?;
+        let broadcasts = self.live_broadcasts.lock().await;
+
+        Ok(stored
+            .into_iter()
+            .map(|stored| {
+                let here = broadcasts.get(&stored.id);
+                LiveBroadcast {
+                    id: stored.id,
+                    owner: stored.owner,
+                    team: stored.team,
+                    controlled_here: here.is_some_and(|broadcast| broadcast.controller.is_some()),
+                    viewers: here
+                        .map(|broadcast| broadcast.subs.iter().copied().collect())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect())
+    }
+
+    /// Whether a shutdown has begun, after which no new broadcasts are started.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Stops new broadcasts, tells everyone connected that the server is restarting, then waits
+    /// for them to leave or for the drain period to run out.
+    pub async fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+        info!(period = ?self.config.drain_period, "Draining live broadcasts");
+
+        let retry_after = self.config.drain_period.as_secs().max(1);
+        // Only this instance is restarting, so the others' viewers needn't hear about it
+        for id in self.live_broadcasts.lock().await.keys() {
+            self.channels
+                .send(id, &format!("state:restarting retry_after={retry_after}"));
+        }
+
+        let deadline = Instant::now() + self.config.drain_period;
+        while Instant::now() < deadline {
+            let connected = self
+                .live_broadcasts
+                .lock()
                 .await
-                .expect("Error initializing database"),
-        })
+                .values()
+                .any(|broadcast| broadcast.controller.is_some() || !broadcast.subs.is_empty());
+            if !connected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Saves the broadcasts controlled from here so the next start can restore them. Ones only
+    /// followed here are left to whichever instance controls them.
+    pub async fn persist_broadcasts(&self) -> Result<(), AppError> {
+        let broadcasts: Vec<_> = self
+            .live_broadcasts
+            .lock()
+            .await
+            .values()
+            .filter(|broadcast| broadcast.hosted)
+            .map(Broadcast::stored)
+            .collect();
+
+        for broadcast in &broadcasts {
+            self.db.save_broadcast(broadcast).await?;
+        }
+        Ok(())
+    }
+
+    /// Saves a broadcast as it changes. A failed save only costs what a crash would have lost,
+    /// so it's logged rather than interrupting the show.
+    pub async fn save_broadcast(&self, broadcast: &StoredBroadcast) {
+        if let Err(err) = self.db.save_broadcast(broadcast).await {
+            warn!(broadcast_id = %broadcast.id, ?err, "Couldn't save broadcast");
+        }
+    }
+
+    /// Sends a message to everyone following a broadcast, on this instance or any other.
+    pub async fn publish(&self, broadcast: &Uuid, message: &str) {
+        if let Err(err) = self.bus.publish(broadcast, message).await {
+            warn!(broadcast_id = %broadcast, ?err, "Couldn't publish to broadcast");
+        }
+    }
+
+    /// Takes the controller lease on a broadcast for this instance, or renews it, for one lease
+    /// period. Returns false if another instance holds it or the broadcast has ended.
+    pub async fn claim_broadcast(&self, id: &Uuid) -> Result<bool, AppError> {
+        let expires_at = Utc::now() + self.config.controller_lease;
+        self.db
+            .claim_broadcast(id, &self.config.instance_id, expires_at)
+            .await
+    }
+
+    /// Lets other instances take the controller straight away, rather than once the lease runs
+    /// out.
+    pub async fn release_broadcast(&self, id: &Uuid) {
+        if let Err(err) = self
+            .db
+            .release_broadcast(id, &self.config.instance_id)
+            .await
+        {
+            warn!(broadcast_id = %id, ?err, "Couldn't release broadcast");
+        }
+    }
+
+    pub async fn forget_broadcast(&self, id: &Uuid) {
+        if let Err(err) = self.db.delete_broadcast(id).await {
+            warn!(broadcast_id = %id, ?err, "Couldn't delete broadcast");
+        }
+    }
+
+    /// Persists the revocation first so a restart can't bring the token back to life.
+    pub async fn revoke_access_token(&self, claims: &AccessTokenClaims) -> Result<(), AppError> {
+        self.db.revoke_token(&claims.jti, claims.exp).await?;
+        self.revoked_tokens.revoke(&claims.jti, claims.exp).await;
+        Ok(())
+    }
+
+    /// An mfa token is spent once it's been exchanged for a session.
+    pub async fn revoke_mfa_token(&self, claims: &MfaTokenClaims) -> Result<(), AppError> {
+        self.db.revoke_token(&claims.jti, claims.exp).await?;
+        self.revoked_tokens.revoke(&claims.jti, claims.exp).await;
+        Ok(())
     }
 }
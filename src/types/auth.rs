@@ -1,4 +1,3 @@
-use crate::types::jwt::JwtManager;
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
@@ -8,11 +7,54 @@ use fancy_regex::Regex;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug)]
+use crate::types::error::AppError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Team {
     pub id: Uuid,
-    pub name: String,
-    _members: Vec<Uuid>,
+    pub name: Option<String>,
+}
+
+/// Ordered by privilege, so `role >= Role::Admin` includes super admins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Member,
+    Admin,
+    /// Runs the platform: may use the admin API across every team.
+    SuperAdmin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Member => "member",
+            Role::Admin => "admin",
+            Role::SuperAdmin => "super_admin",
+        }
+    }
+}
+
+impl From<&str> for Role {
+    fn from(role: &str) -> Self {
+        match role {
+            "admin" => Role::Admin,
+            "super_admin" => Role::SuperAdmin,
+            _ => Role::Member,
+        }
+    }
+}
+
+/// An account as seen by request handlers, without its credentials.
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub role: Role,
+    pub team: Option<Team>,
+    /// Set by an operator; a disabled account can't sign in or use its tokens.
+    pub disabled: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -21,62 +63,31 @@ pub struct Auth {
     pub team: Option<Uuid>,
     pub email: String,
     pub hash: String,
-    pub refresh_token: String,
 }
 
 impl Auth {
-    pub fn new(request: UserRegistrationRequest) -> (Self, String) {
-        let argon2 = Argon2::default();
-        let salt = SaltString::generate(&mut OsRng);
-
-        let hash = argon2
-            .hash_password(request.password.as_bytes(), &salt)
-            .unwrap()
-            .to_string();
-
-        let mut auth = Self {
+    pub fn new(request: UserRegistrationRequest) -> Result<Self, AppError> {
+        Ok(Self {
             id: uuid::Uuid::new_v4(),
-            team: None,
+            team: request.team,
+            hash: Self::hash_password(&request.password)?,
             email: request.email,
-            hash,
-            refresh_token: String::new(),
-        };
-
-        auth.refresh_token = JwtManager::new_refresh_token(&auth.id.to_string()).unwrap();
-        let access_token = JwtManager::new_access_token(&auth.id.to_string())
-            .expect("error creating access token");
-
-        (auth, access_token)
+        })
     }
 
-    pub fn new_with_team(request: UserRegistrationRequest) -> (Self, String) {
+    pub fn hash_password(password: &str) -> Result<String, AppError> {
         let argon2 = Argon2::default();
         let salt = SaltString::generate(&mut OsRng);
 
-        let hash = argon2
-            .hash_password(request.password.as_bytes(), &salt)
-            .unwrap()
-            .to_string();
-
-        let mut auth = Self {
-            id: uuid::Uuid::new_v4(),
-            team: request.team,
-            email: request.email,
-            hash,
-            refresh_token: String::new(),
-        };
-
-        auth.refresh_token = JwtManager::new_refresh_token(&auth.refresh_token).unwrap();
-        let access_token = JwtManager::new_access_token(&auth.id.to_string())
-            .expect("error creating access token");
-
-        (auth, access_token)
+        argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(AppError::internal)
     }
 
     pub fn verify_password(password: &[u8], hash: &str) -> bool {
-        Argon2::default()
-            .verify_password(password, &PasswordHash::new(hash).unwrap())
-            .is_ok()
+        PasswordHash::new(hash)
+            .is_ok_and(|hash| Argon2::default().verify_password(password, &hash).is_ok())
     }
 
     pub fn validate_email(email: &str) -> bool {
@@ -95,6 +106,7 @@ impl Auth {
 pub struct UserAccessRequest {
     pub email: String,
     pub password: String,
+    pub device: Option<String>,
 }
 
 #[derive(Default, Deserialize, Serialize, Debug)]
@@ -102,6 +114,7 @@ pub struct UserRegistrationRequest {
     pub email: String,
     pub password: String,
     pub team: Option<Uuid>,
+    pub device: Option<String>,
 }
 
 #[derive(Debug, Default, Serialize)]
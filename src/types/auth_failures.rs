@@ -0,0 +1,57 @@
+use std::{collections::VecDeque, net::IpAddr};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use super::AppError;
+
+/// How many failures are kept. Older ones are dropped as new ones come in.
+const KEPT: usize = 200;
+
+/// A sign-in that didn't succeed, as shown to operators.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthFailure {
+    pub at: DateTime<Utc>,
+    /// `password`, `totp` or `oidc`.
+    pub method: &'static str,
+    /// The email tried, or the account id once the password has been accepted.
+    pub account: Option<String>,
+    pub ip: Option<IpAddr>,
+    /// The error code the client was given, such as `invalid_credentials` or `rate_limited`.
+    pub reason: &'static str,
+}
+
+/// The latest failed sign-ins on this instance, for spotting credential stuffing or a user
+/// who's locked themselves out.
+#[derive(Debug, Default)]
+pub struct AuthFailureLog {
+    failures: Mutex<VecDeque<AuthFailure>>,
+}
+
+impl AuthFailureLog {
+    pub async fn record(
+        &self,
+        method: &'static str,
+        account: Option<&str>,
+        ip: Option<IpAddr>,
+        err: &AppError,
+    ) {
+        let mut failures = self.failures.lock().await;
+        if failures.len() == KEPT {
+            failures.pop_back();
+        }
+        failures.push_front(AuthFailure {
+            at: Utc::now(),
+            method,
+            account: account.map(str::to_string),
+            ip,
+            reason: err.code(),
+        });
+    }
+
+    /// Newest first.
+    pub async fn recent(&self) -> Vec<AuthFailure> {
+        self.failures.lock().await.iter().cloned().collect()
+    }
+}
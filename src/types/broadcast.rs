@@ -2,10 +2,15 @@ use std::{collections::HashSet, net::SocketAddr, sync::Arc};
 
 use axum::extract::ws::{Message, WebSocket};
 use futures::{SinkExt, StreamExt};
-use tokio::sync::broadcast::{channel, Sender};
+use tokio::{
+    sync::{broadcast::error::RecvError, watch},
+    task::JoinHandle,
+};
+use tracing::{debug, field, info, warn, Instrument, Span};
 use uuid::Uuid;
 
-use super::application_state::ApplicationState;
+use super::{application_state::ApplicationState, repository::StoredBroadcast, AppError};
+use crate::handlers::Principal;
 
 #[allow(non_snake_case, non_upper_case_globals)]
 mod BroadcastCommands {
@@ -21,134 +26,676 @@ mod BroadcastCommands {
     pub const HardWrap: &str = "timing:hard_wrap";
     pub const ResetTiming: &str = "timing:reset";
     pub const End: &str = "state:end";
+    /// Sent to a viewer an operator has disconnected.
+    pub const Kicked: &str = "state:kicked";
+    /// Followed by a viewer's address. Goes to every instance so the one the viewer is on can
+    /// disconnect it, and is never passed on to clients.
+    pub const Kick: &str = "state:kick ";
+    /// Followed by how far into the script the controller has scrolled, e.g. `position:1200`.
+    pub const Position: &str = "position:";
+    /// Followed by whatever the controller's client uses to find the script it has loaded, such
+    /// as its id or file name, e.g. `script:evening-news.txt`. Kept as sent, case included.
+    pub const Script: &str = "script:";
+}
+
+/// Longest script reference kept, in characters. It's stored in a `VARCHAR(255)`.
+const MAX_SCRIPT_REFERENCE: usize = 255;
+
+/// Where a show is up to, kept so anyone joining or rejoining can be brought up to date.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BroadcastState {
+    /// The script loaded, as the controller last named it with `script:`.
+    pub script: Option<String>,
+    /// From 1 to 5, once the controller has picked one.
+    pub speed: Option<i16>,
+    /// `scroll` toggles scrolling on and off.
+    pub scrolling: bool,
+    /// The position the controller last reported.
+    pub position: Option<i32>,
+    /// The last timing cue, until `timing:reset`.
+    pub timer: Option<String>,
+}
+
+impl BroadcastState {
+    /// Records a controller command, returning false if it isn't one. Commands are matched
+    /// ignoring case.
+    fn apply(&mut self, message: &str) -> bool {
+        let lowercase = message.to_lowercase();
+        let command = lowercase.as_str();
+        if command.starts_with(BroadcastCommands::Script) {
+            let script = message[BroadcastCommands::Script.len()..].trim();
+            if script.is_empty() || script.chars().count() > MAX_SCRIPT_REFERENCE {
+                return false;
+            }
+            self.script = Some(script.to_string());
+            return true;
+        }
+
+        match command {
+            BroadcastCommands::Scroll => self.scrolling = !self.scrolling,
+            BroadcastCommands::ScrollSpeed1 => self.speed = Some(1),
+            BroadcastCommands::ScrollSpeed2 => self.speed = Some(2),
+            BroadcastCommands::ScrollSpeed3 => self.speed = Some(3),
+            BroadcastCommands::ScrollSpeed4 => self.speed = Some(4),
+            BroadcastCommands::ScrollSpeed5 => self.speed = Some(5),
+            BroadcastCommands::OneMinute
+            | BroadcastCommands::ThirtySeconds
+            | BroadcastCommands::Wrap
+            | BroadcastCommands::HardWrap => self.timer = Some(command.to_string()),
+            BroadcastCommands::ResetTiming => self.timer = None,
+            _ => {
+                let Some(position) = command
+                    .strip_prefix(BroadcastCommands::Position)
+                    .and_then(|position| position.parse().ok())
+                    .filter(|position| *position >= 0)
+                else {
+                    return false;
+                };
+                self.position = Some(position);
+            }
+        }
+        true
+    }
+
+    /// The commands that bring a client that has just connected to this state.
+    fn replay(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        if let Some(script) = &self.script {
+            commands.push(format!("{}{script}", BroadcastCommands::Script));
+        }
+        if let Some(speed) = self.speed {
+            commands.push(format!("scroll:speed_{speed}"));
+        }
+        if let Some(position) = self.position {
+            commands.push(format!("{}{position}", BroadcastCommands::Position));
+        }
+        if let Some(timer) = &self.timer {
+            commands.push(timer.clone());
+        }
+        if self.scrolling {
+            commands.push(BroadcastCommands::Scroll.to_string());
+        }
+        commands
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Broadcast {
     pub id: Uuid,
+    pub owner: Option<Uuid>,
+    pub team: Option<Uuid>,
+    /// Where the controller is connected from, while it is.
+    pub controller: Option<SocketAddr>,
+    /// Viewers connected to this instance.
     pub subs: HashSet<SocketAddr>,
-    pub transmitter: Sender<String>,
+    pub state: BroadcastState,
+    /// Whether the controller has connected to this instance. Until it does, the broadcast is
+    /// only followed here and saving it is left to whichever instance hosts it.
+    pub hosted: bool,
 }
 
 impl Broadcast {
-    fn new(initial_subscriber: SocketAddr) -> Self {
-        let mut broadcast = Self {
+    fn new(principal: &Principal) -> Self {
+        Self {
             id: Uuid::new_v4(),
+            owner: principal.user_id(),
+            team: principal.team(),
+            controller: None,
+            subs: HashSet::new(),
+            state: BroadcastState::default(),
+            hosted: true,
+        }
+    }
+
+    /// Brings back a saved broadcast, to follow until its controller resumes it here.
+    pub fn restore(stored: StoredBroadcast) -> Self {
+        Self {
+            id: stored.id,
+            owner: stored.owner,
+            team: stored.team,
+            controller: None,
             subs: HashSet::new(),
-            transmitter: channel(11).0,
+            state: stored.state,
+            hosted: false,
+        }
+    }
+
+    pub fn stored(&self) -> StoredBroadcast {
+        StoredBroadcast {
+            id: self.id,
+            owner: self.owner,
+            team: self.team,
+            state: self.state.clone(),
+            lease: None,
+        }
+    }
+
+    /// Applies a controller message to the live broadcast, returning the state to save, or
+    /// `None` if the command isn't valid.
+    async fn record(
+        state: &Arc<ApplicationState>,
+        broadcast_id: &Uuid,
+        message: &str,
+    ) -> Option<StoredBroadcast> {
+        let stored = {
+            let mut broadcasts = state.live_broadcasts.lock().await;
+            let broadcast = broadcasts.get_mut(broadcast_id)?;
+            if !broadcast.state.apply(message) {
+                state.metrics.command("invalid");
+                return None;
+            }
+            broadcast.stored()
         };
-        broadcast.subs.insert(initial_subscriber);
-        broadcast
+
+        // Positions and scripts name anything, so each kind is counted together
+        let command = message.to_lowercase();
+        let kind = if command.starts_with(BroadcastCommands::Position) {
+            "position"
+        } else if command.starts_with(BroadcastCommands::Script) {
+            "script"
+        } else {
+            &command
+        };
+        state.metrics.command(kind);
+        Some(stored)
     }
 
-    async fn verify_live(state: &Arc<ApplicationState>, broadcast_id: &str) -> bool {
-        let broadcasts = state.live_broadcasts.lock().await;
-        if Uuid::parse_str(broadcast_id).is_err() {
-            return false;
+    /// Saves a controller's changes in the background, so commands are relayed without waiting
+    /// on the database. Changes made while a save is under way are folded into the next one.
+    /// The task finishes once the sender is dropped and the last change is saved.
+    fn saver(
+        state: Arc<ApplicationState>,
+        stored: StoredBroadcast,
+    ) -> (watch::Sender<StoredBroadcast>, JoinHandle<()>) {
+        let (saves, mut latest) = watch::channel(stored);
+        let task = tokio::spawn(
+            async move {
+                while latest.changed().await.is_ok() {
+                    let stored = latest.borrow_and_update().clone();
+                    state.save_broadcast(&stored).await;
+                }
+            }
+            .in_current_span(),
+        );
+        (saves, task)
+    }
+
+    /// Users resume their own broadcasts; API keys resume ones their team started with a key.
+    fn may_resume(&self, principal: &Principal) -> bool {
+        match self.owner {
+            Some(owner) => principal.user_id() == Some(owner),
+            None => self.team.is_some() && principal.team() == self.team,
         }
+    }
 
-        if !broadcasts.contains_key(&Uuid::parse_str(broadcast_id).unwrap()) {
-            return false;
+    /// Whether the broadcast is live on any instance. Unless its controller is connected here,
+    /// the local entry is brought up to date from the registry, or dropped if it has ended.
+    async fn locate(state: &Arc<ApplicationState>, broadcast_id: &Uuid) -> bool {
+        {
+            let broadcasts = state.live_broadcasts.lock().await;
+            let controlled_here = broadcasts
+                .get(broadcast_id)
+                .is_some_and(|broadcast| broadcast.controller.is_some());
+            if controlled_here {
+                return true;
+            }
+        }
+
+        let stored = match state.db.broadcast(broadcast_id).await {
+            Ok(stored) => stored,
+            Err(err) => {
+                warn!(%broadcast_id, ?err, "Couldn't look up broadcast");
+                return state
+                    .live_broadcasts
+                    .lock()
+                    .await
+                    .contains_key(broadcast_id);
+            }
+        };
+
+        let mut broadcasts = state.live_broadcasts.lock().await;
+        match stored {
+            Some(stored) => {
+                let broadcast = broadcasts
+                    .entry(*broadcast_id)
+                    .or_insert_with(|| Self::restore(stored.clone()));
+                if broadcast.controller.is_none() {
+                    broadcast.state = stored.state;
+                }
+                true
+            }
+            None => {
+                let ended = broadcasts
+                    .get(broadcast_id)
+                    .is_some_and(|broadcast| broadcast.controller.is_none());
+                if ended {
+                    broadcasts.remove(broadcast_id);
+                }
+                broadcasts.contains_key(broadcast_id)
+            }
         }
-        true
     }
 
-    pub async fn init(socket: WebSocket, who: SocketAddr, state: Arc<ApplicationState>) {
-        let (mut client_sender, mut client_receiver) = socket.split();
+    /// Checks a controller may take over a broadcast, before its socket is upgraded.
+    pub async fn verify_resumable(
+        state: &Arc<ApplicationState>,
+        broadcast_id: &Uuid,
+        principal: &Principal,
+    ) -> Result<(), AppError> {
+        Self::locate(state, broadcast_id).await;
 
-        // Create new broadcast and subscribe to broadcast transmitter
-        let broadcast = Self::new(who);
-        let mut receiver = broadcast.transmitter.subscribe();
+        let broadcasts = state.live_broadcasts.lock().await;
+        let Some(broadcast) = broadcasts.get(broadcast_id) else {
+            return Err(AppError::NotFound("Broadcast"));
+        };
 
+        if !broadcast.may_resume(principal) {
+            return Err(AppError::Forbidden(
+                "Only whoever started a broadcast can resume it".to_string(),
+            ));
+        }
+        if broadcast.controller.is_some() {
+            return Err(AppError::Conflict(
+                "Broadcast already has a controller".to_string(),
+            ));
+        }
+        drop(broadcasts);
+
+        // A controller on another instance holds a lease in the registry until it disconnects
+        let lease = state
+            .db
+            .broadcast(broadcast_id)
+            .await?
+            .and_then(|stored| stored.lease);
+        if lease.is_some_and(|lease| lease.instance != state.config.instance_id && lease.is_live())
         {
-            // Add new broadcast to table of live broadcasts then alert client
+            return Err(AppError::Conflict(
+                "Broadcast already has a controller".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Renews the controller lease until it's lost, resolving to whether that's because the
+    /// broadcast was ended elsewhere rather than taken over.
+    fn heartbeat(state: Arc<ApplicationState>, broadcast_id: Uuid) -> JoinHandle<bool> {
+        tokio::spawn(
+            async move {
+                let mut renewals = tokio::time::interval(state.config.controller_lease / 3);
+                renewals.tick().await;
+                loop {
+                    renewals.tick().await;
+                    match state.claim_broadcast(&broadcast_id).await {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(err) => warn!(?err, "Couldn't renew controller lease"),
+                    }
+                }
+
+                matches!(state.db.broadcast(&broadcast_id).await, Ok(None))
+            }
+            .in_current_span(),
+        )
+    }
+
+    /// Ends a broadcast for an operator, wherever its controller is connected. Everyone
+    /// following it is sent `state:end`, the controller included.
+    pub async fn force_end(
+        state: &Arc<ApplicationState>,
+        broadcast_id: &Uuid,
+    ) -> Result<(), AppError> {
+        if !Self::locate(state, broadcast_id).await {
+            return Err(AppError::NotFound("Broadcast"));
+        }
+
+        info!(%broadcast_id, "Operator ended broadcast");
+        state.publish(broadcast_id, BroadcastCommands::End).await;
+        // Listeners here still get the end, then find the channel closed
+        state.live_broadcasts.lock().await.remove(broadcast_id);
+        state.channels.close(broadcast_id);
+        state.forget_broadcast(broadcast_id).await;
+        Ok(())
+    }
+
+    /// Disconnects a viewer on whichever instance it's connected to, by asking all of them over
+    /// the bus. Returns whether the viewer was connected to this one.
+    pub async fn kick(
+        state: &ApplicationState,
+        broadcast_id: &Uuid,
+        viewer: &SocketAddr,
+    ) -> Result<bool, AppError> {
+        let here = state
+            .live_broadcasts
+            .lock()
+            .await
+            .get(broadcast_id)
+            .is_some_and(|broadcast| broadcast.subs.contains(viewer));
+        if !here && state.db.broadcast(broadcast_id).await?.is_none() {
+            return Err(AppError::NotFound("Broadcast"));
+        }
+
+        info!(%broadcast_id, %viewer, "Operator kicked viewer");
+        let kick = format!("{}{viewer}", BroadcastCommands::Kick);
+        state.publish(broadcast_id, &kick).await;
+        Ok(here)
+    }
+
+    pub async fn init(
+        socket: WebSocket,
+        who: SocketAddr,
+        principal: Principal,
+        resume: Option<Uuid>,
+        state: Arc<ApplicationState>,
+    ) {
+        let (mut client_sender, mut client_receiver) = socket.split();
+
+        // Take over the broadcast being resumed, or add a new one to the table of live broadcasts.
+        // Another controller may have resumed it, or it may have ended, since the socket was
+        // upgraded, so the checks are repeated now the table is locked.
+        let taken = {
             let mut broadcasts = state.live_broadcasts.lock().await;
-            broadcasts.insert(broadcast.id, broadcast.clone());
-            let _ = broadcast
-                .transmitter
-                .send(format!("Broadcast {} started. {who} joined.", broadcast.id));
-        }
-
-        // Receive messages from Broadcast and send message to client
-        let mut send_task = tokio::spawn(async move {
-            while let Ok(msg) = receiver.recv().await {
-                // Break loop for any websocket error
-                if client_sender.send(Message::Text(msg)).await.is_err() {
-                    break;
+            let broadcast = match resume {
+                Some(id) => match broadcasts.get_mut(&id) {
+                    None => Err("Broadcast doesn't exist!"),
+                    Some(broadcast) if !broadcast.may_resume(&principal) => {
+                        Err("Only whoever started a broadcast can resume it!")
+                    }
+                    Some(broadcast) if broadcast.controller.is_some() => {
+                        Err("Broadcast already has a controller!")
+                    }
+                    Some(broadcast) => Ok(broadcast),
+                },
+                None => {
+                    let broadcast = Self::new(&principal);
+                    Ok(broadcasts.entry(broadcast.id).or_insert(broadcast))
+                }
+            };
+
+            broadcast.map(|broadcast| {
+                broadcast.controller = Some(who);
+                broadcast.hosted = true;
+
+                let verb = if resume.is_some() {
+                    "resumed"
+                } else {
+                    "started"
+                };
+                let greeting = format!("Broadcast {} {verb}. {who} joined.", broadcast.id);
+                (broadcast.stored(), verb, greeting, broadcast.state.replay())
+            })
+        };
+        let (stored, verb, greeting, replay) = match taken {
+            Ok(taken) => taken,
+            Err(refusal) => {
+                info!(refusal, "Refused controller");
+                let _ = client_sender.send(Message::Text(refusal.to_string())).await;
+                return;
+            }
+        };
+        let broadcast_id = stored.id;
+        Span::current().record("broadcast_id", field::display(broadcast_id));
+
+        // A new broadcast has to be saved before it can be claimed. One being resumed is claimed
+        // first, so that if it was ended elsewhere in the meantime it isn't saved back.
+        if resume.is_none() {
+            state.save_broadcast(&stored).await;
+        }
+        let claimed = state
+            .claim_broadcast(&broadcast_id)
+            .await
+            .unwrap_or_else(|err| {
+                warn!(?err, "Couldn't claim controller lease");
+                false
+            });
+        if !claimed {
+            if let Some(broadcast) = state.live_broadcasts.lock().await.get_mut(&broadcast_id) {
+                if broadcast.controller == Some(who) {
+                    broadcast.controller = None;
+                }
+            }
+            let refusal = match state.db.broadcast(&broadcast_id).await {
+                Ok(None) => "Broadcast doesn't exist!",
+                _ => "Broadcast already has a controller!",
+            };
+            info!(refusal, "Refused controller");
+            let _ = client_sender.send(Message::Text(refusal.to_string())).await;
+            return;
+        }
+        if resume.is_some() {
+            state.save_broadcast(&stored).await;
+        }
+        info!("Broadcast {verb}");
+
+        // Let any viewers know before the controller subscribes, so it isn't told twice
+        state.publish(&broadcast_id, &greeting).await;
+        let mut receiver = state.channels.subscribe(&broadcast_id);
+
+        // Alert the client, catching it up if it's picking up where it left off. Resolves to
+        // whether an operator ended the broadcast.
+        let metrics = state.metrics.clone();
+        let mut send_task = tokio::spawn(
+            async move {
+                let mut replay = std::iter::once(greeting).chain(replay);
+                loop {
+                    // Then receive messages from Broadcast and send them to the client
+                    let msg = match replay.next() {
+                        Some(msg) => msg,
+                        None => match receiver.recv().await {
+                            Ok(msg) => msg,
+                            Err(RecvError::Lagged(missed)) => {
+                                metrics.lagged(missed);
+                                break;
+                            }
+                            Err(RecvError::Closed) => break,
+                        },
+                    };
+                    // Kicks are for viewers
+                    if msg.starts_with(BroadcastCommands::Kick) {
+                        continue;
+                    }
+
+                    // Break loop for any websocket error
+                    let ended = msg == BroadcastCommands::End;
+                    if client_sender.send(Message::Text(msg)).await.is_err() {
+                        break;
+                    }
+                    if ended {
+                        return true;
+                    }
                 }
+                false
             }
-        });
+            .in_current_span(),
+        );
 
         // Receive message from client and send to broadcast subscribers
-        let transmitter = broadcast.transmitter.clone();
-
-        let mut recv_task = tokio::spawn(async move {
-            while let Some(Ok(Message::Text(msg))) = client_receiver.next().await {
-                match msg.to_lowercase().as_str() {
-                    BroadcastCommands::Scroll
-                    | BroadcastCommands::ScrollSpeed1
-                    | BroadcastCommands::ScrollSpeed2
-                    | BroadcastCommands::ScrollSpeed3
-                    | BroadcastCommands::ScrollSpeed4
-                    | BroadcastCommands::ScrollSpeed5
-                    | BroadcastCommands::OneMinute
-                    | BroadcastCommands::ThirtySeconds
-                    | BroadcastCommands::Wrap
-                    | BroadcastCommands::HardWrap
-                    | BroadcastCommands::ResetTiming => {
-                        let _ = transmitter.send(msg.to_string());
+        let recorder = state.clone();
+        let (saves, saver) = Self::saver(state.clone(), stored);
+
+        // Resolves to whether the controller ended the broadcast, rather than just leaving
+        let mut recv_task = tokio::spawn(
+            async move {
+                while let Some(Ok(Message::Text(msg))) = client_receiver.next().await {
+                    let command = msg.to_lowercase();
+                    if command == BroadcastCommands::End {
+                        return true;
                     }
-                    BroadcastCommands::End => break,
-                    _ => {
-                        let _ = transmitter.send("Invalid message".to_string());
+
+                    match Self::record(&recorder, &broadcast_id, &msg).await {
+                        Some(stored) => {
+                            debug!(%command, "Relaying command");
+                            recorder.publish(&broadcast_id, &msg).await;
+                            saves.send_replace(stored);
+                        }
+                        None => {
+                            debug!(%command, "Rejected command");
+                            recorder.publish(&broadcast_id, "Invalid message").await;
+                        }
                     }
                 }
+                false
             }
-        });
+            .in_current_span(),
+        );
 
-        // If one task ends, the other is aborted
-        tokio::select! {
-            _ = (&mut send_task) => recv_task.abort(),
-            _ = (&mut recv_task) => send_task.abort(),
+        // If one task ends, the other is aborted. Losing the lease ends both.
+        let mut heartbeat = Self::heartbeat(state.clone(), broadcast_id);
+        let (ended, forced, lost) = tokio::select! {
+            forced = (&mut send_task) => {
+                recv_task.abort();
+                let forced = forced.unwrap_or(false);
+                (forced, forced, false)
+            }
+            ended = (&mut recv_task) => {
+                send_task.abort();
+                (ended.unwrap_or(false), false, false)
+            }
+            gone = (&mut heartbeat) => {
+                send_task.abort();
+                recv_task.abort();
+                let gone = gone.unwrap_or(false);
+                warn!(gone, "Lost the controller lease");
+                (gone, gone, !gone)
+            }
         };
+        heartbeat.abort();
+
+        // Give the lease up before the controller's seat here, so a controller resuming on
+        // this instance can't have its new lease released
+        if !ended {
+            state.release_broadcast(&broadcast_id).await;
+        }
+
+        // An ended broadcast goes away, closing its viewers. One whose controller just dropped
+        // stays live so the controller can resume it.
+        if ended && !forced {
+            state.publish(&broadcast_id, BroadcastCommands::End).await;
+        }
+        let mut broadcasts = state.live_broadcasts.lock().await;
+        if ended {
+            broadcasts.remove(&broadcast_id);
+            state.channels.close(&broadcast_id);
+        } else if let Some(broadcast) = broadcasts.get_mut(&broadcast_id) {
+            if broadcast.controller == Some(who) {
+                broadcast.controller = None;
+            }
+            // Saving it is now up to the instance that took it over
+            if lost {
+                broadcast.hosted = false;
+            }
+        }
+        drop(broadcasts);
+
+        // A save still under way would bring an ended broadcast back
+        if ended {
+            let _ = saver.await;
+            state.forget_broadcast(&broadcast_id).await;
+        }
 
-        println!("Websocket context {who} destroyed");
+        info!(ended, forced, "Controller disconnected");
     }
 
     pub async fn subscribe(socket: WebSocket, who: SocketAddr, state: Arc<ApplicationState>) {
         let (mut client_sender, mut client_receiver) = socket.split();
-        let mut broadcast_id = String::new();
+        let mut broadcast_id = None;
 
         // Verify broadcast is live with given id
         while let Some(Ok(message)) = client_receiver.next().await {
             if let Message::Text(id) = message {
-                if !Self::verify_live(&state, &id).await {
+                let id = Uuid::parse_str(&id).ok();
+                let live = match &id {
+                    Some(id) => Self::locate(&state, id).await,
+                    None => false,
+                };
+                if !live {
                     let _ = client_sender
                         .send(Message::Text(String::from("Broadcast doesn't exist!")))
                         .await;
                     return;
                 }
-                broadcast_id.push_str(&id);
+                broadcast_id = id;
                 break;
             }
         }
 
-        // Subscribe client to live broadcast
+        // Subscribe client to live broadcast. The client may have hung up before naming one.
+        let Some(broadcast_id) = broadcast_id else {
+            return;
+        };
+        Span::current().record("broadcast_id", field::display(broadcast_id));
         let mut live_broadcasts = state.live_broadcasts.lock().await;
-        let broadcast = live_broadcasts
-            .get_mut(&Uuid::parse_str(broadcast_id.as_str()).unwrap())
-            .unwrap();
-        let mut receiver = broadcast.transmitter.subscribe();
+        // The last viewer of a broadcast hosted elsewhere may have dropped it since it was located
+        let Some(broadcast) = live_broadcasts.get_mut(&broadcast_id) else {
+            drop(live_broadcasts);
+            let _ = client_sender
+                .send(Message::Text(String::from("Broadcast doesn't exist!")))
+                .await;
+            return;
+        };
+
+        if broadcast.subs.len() >= state.config.max_viewers {
+            drop(live_broadcasts);
+            info!("Turned viewer away from a full broadcast");
+            let _ = client_sender
+                .send(Message::Text(String::from("Broadcast is full!")))
+                .await;
+            return;
+        }
+
+        let mut receiver = state.channels.subscribe(&broadcast_id);
         broadcast.subs.insert(who);
+        let replay = broadcast.state.replay();
+        drop(live_broadcasts);
+        info!("Viewer joined");
 
-        // Receive messages from Broadcast and send message to client
-        tokio::spawn(async move {
-            while let Ok(msg) = receiver.recv().await {
-                // Break loop for any websocket error
-                if client_sender.send(Message::Text(msg)).await.is_err() {
-                    break;
+        tokio::spawn(
+            async move {
+                // Catch the viewer up on where the show is, then follow along
+                let mut replay = replay.into_iter();
+                loop {
+                    let msg = match replay.next() {
+                        Some(msg) => msg,
+                        None => match receiver.recv().await {
+                            Ok(msg) => msg,
+                            Err(RecvError::Lagged(missed)) => {
+                                state.metrics.lagged(missed);
+                                break;
+                            }
+                            Err(RecvError::Closed) => break,
+                        },
+                    };
+                    let msg = match msg.strip_prefix(BroadcastCommands::Kick) {
+                        Some(viewer) if viewer == who.to_string() => {
+                            BroadcastCommands::Kicked.to_string()
+                        }
+                        Some(_) => continue,
+                        None => msg,
+                    };
+
+                    // Break loop for any websocket error, or once the viewer is done
+                    let ended = msg == BroadcastCommands::End || msg == BroadcastCommands::Kicked;
+                    if client_sender.send(Message::Text(msg)).await.is_err() || ended {
+                        break;
+                    }
+                }
+
+                // Free the viewer's seat, forgetting a broadcast hosted elsewhere once nobody here follows it
+                let mut broadcasts = state.live_broadcasts.lock().await;
+                if let Some(broadcast) = broadcasts.get_mut(&broadcast_id) {
+                    broadcast.subs.remove(&who);
+                    if !broadcast.hosted
+                        && broadcast.controller.is_none()
+                        && broadcast.subs.is_empty()
+                    {
+                        broadcasts.remove(&broadcast_id);
+                        state.channels.close(&broadcast_id);
+                    }
                 }
+                drop(broadcasts);
+                info!("Viewer left");
             }
-        });
+            .in_current_span(),
+        );
     }
 }
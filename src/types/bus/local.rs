@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::{BroadcastBus, Channels};
+use crate::types::AppError;
+
+/// Keeps broadcasts within one instance. Right for a single server.
+#[derive(Debug)]
+pub struct LocalBus {
+    channels: Arc<Channels>,
+}
+
+impl LocalBus {
+    pub fn new(channels: Arc<Channels>) -> Self {
+        Self { channels }
+    }
+}
+
+#[async_trait]
+impl BroadcastBus for LocalBus {
+    async fn publish(&self, broadcast: &Uuid, message: &str) -> Result<(), AppError> {
+        self.channels.send(broadcast, message);
+        Ok(())
+    }
+}
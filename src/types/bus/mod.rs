@@ -0,0 +1,70 @@
+mod local;
+mod redis;
+
+use std::{collections::HashMap, error::Error, fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use tokio::sync::broadcast::{channel, Receiver, Sender};
+use uuid::Uuid;
+
+pub use self::{local::LocalBus, redis::RedisBus};
+use super::AppError;
+
+/// Messages a broadcast channel holds for viewers that fall behind.
+const CHANNEL_CAPACITY: usize = 11;
+
+/// The channels this instance's controllers and viewers listen on, one per broadcast.
+#[derive(Debug, Default)]
+pub struct Channels {
+    senders: std::sync::Mutex<HashMap<Uuid, Sender<String>>>,
+}
+
+impl Channels {
+    fn senders(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, Sender<String>>> {
+        self.senders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn subscribe(&self, broadcast: &Uuid) -> Receiver<String> {
+        self.senders()
+            .entry(*broadcast)
+            .or_insert_with(|| channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Delivers to listeners on this instance only.
+    pub fn send(&self, broadcast: &Uuid, message: &str) {
+        if let Some(sender) = self.senders().get(broadcast) {
+            let _ = sender.send(message.to_string());
+        }
+    }
+
+    /// Listeners get whatever was already sent, then find the channel closed.
+    pub fn close(&self, broadcast: &Uuid) {
+        self.senders().remove(broadcast);
+    }
+}
+
+/// Carries broadcast messages between instances, so a controller and its viewers can be
+/// connected to different ones.
+#[async_trait]
+pub trait BroadcastBus: Send + Sync + Debug {
+    /// Delivers a message to the broadcast's listeners on this instance straight away, and on
+    /// every other instance through the bus.
+    async fn publish(&self, broadcast: &Uuid, message: &str) -> Result<(), AppError>;
+}
+
+/// Picks the bus from the URL scheme.
+pub async fn connect(
+    bus_url: &str,
+    channels: Arc<Channels>,
+) -> Result<Box<dyn BroadcastBus>, Box<dyn Error + Send + Sync>> {
+    let scheme = bus_url.split(':').next().unwrap_or_default();
+
+    match scheme {
+        "local" => Ok(Box::new(LocalBus::new(channels))),
+        "redis" | "rediss" => Ok(Box::new(RedisBus::connect(bus_url, channels).await?)),
+        _ => Err(format!("Unsupported broadcast bus URL scheme `{scheme}`").into()),
+    }
+}
@@ -0,0 +1,100 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use redis::{aio::MultiplexedConnection, AsyncCommands, Client, RedisResult};
+use tokio::task::JoinHandle;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{BroadcastBus, Channels};
+use crate::types::AppError;
+
+/// Each broadcast is published on its own Redis channel, named with this and its id.
+const CHANNEL_PREFIX: &str = "livescript:broadcast:";
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Relays broadcasts between instances over Redis pub/sub. Every instance listens to every
+/// broadcast and hands messages to whichever of its viewers follow it.
+#[derive(Debug)]
+pub struct RedisBus {
+    /// Tags what this instance publishes, so it can skip its own messages coming back.
+    instance: Uuid,
+    connection: MultiplexedConnection,
+    channels: Arc<Channels>,
+    listener: JoinHandle<()>,
+}
+
+impl RedisBus {
+    pub async fn connect(url: &str, channels: Arc<Channels>) -> RedisResult<Self> {
+        let client = Client::open(url)?;
+        let connection = client.get_multiplexed_tokio_connection().await?;
+        let instance = Uuid::new_v4();
+        let listener = tokio::spawn(Self::listen(client, instance, channels.clone()));
+
+        Ok(Self {
+            instance,
+            connection,
+            channels,
+            listener,
+        })
+    }
+
+    /// Forwards other instances' messages for as long as the bus lives, reconnecting as needed.
+    async fn listen(client: Client, instance: Uuid, channels: Arc<Channels>) {
+        loop {
+            if let Err(err) = Self::forward(&client, instance, &channels).await {
+                warn!(%err, "Broadcast bus subscription failed");
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn forward(client: &Client, instance: Uuid, channels: &Channels) -> RedisResult<()> {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.psubscribe(format!("{CHANNEL_PREFIX}*")).await?;
+
+        let mut messages = pubsub.into_on_message();
+        while let Some(message) = messages.next().await {
+            let Some(broadcast) = message
+                .get_channel_name()
+                .strip_prefix(CHANNEL_PREFIX)
+                .and_then(|id| Uuid::parse_str(id).ok())
+            else {
+                continue;
+            };
+
+            let payload: String = message.get_payload()?;
+            let Some((origin, text)) = payload.split_once(' ') else {
+                continue;
+            };
+            if origin != instance.to_string() {
+                channels.send(&broadcast, text);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for RedisBus {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+#[async_trait]
+impl BroadcastBus for RedisBus {
+    async fn publish(&self, broadcast: &Uuid, message: &str) -> Result<(), AppError> {
+        self.channels.send(broadcast, message);
+
+        self.connection
+            .clone()
+            .publish(
+                format!("{CHANNEL_PREFIX}{broadcast}"),
+                format!("{} {message}", self.instance),
+            )
+            .await
+            .map_err(|err| AppError::Upstream(format!("Broadcast bus: {err}")))
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use uuid::Uuid;
+
+use crate::types::oidc::OidcConfig;
+
+/// Read when `LIVESCRIPT_CONFIG` doesn't point somewhere else.
+const DEFAULT_CONFIG_FILE: &str = "livescript.toml";
+const MIN_SECRET_LENGTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct TokenConfig {
+    pub access_secret: String,
+    pub refresh_secret: String,
+    pub access_ttl: Duration,
+    pub refresh_ttl: Duration,
+}
+
+/// PEM files to serve HTTPS and WSS with, instead of plain HTTP.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// How often the files are checked for a renewed certificate.
+    pub reload_interval: Duration,
+}
+
+/// How log lines are written to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human readable, one line per event.
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Everything the server needs to start, loaded and validated once.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind_address: SocketAddr,
+    pub database_url: String,
+    /// Where broadcasts are relayed between instances; `local:` when there's only one.
+    pub bus_url: String,
+    /// Apply pending migrations at startup instead of with `livescript migrate`.
+    pub auto_migrate: bool,
+    pub tokens: TokenConfig,
+    /// Marks cookies `Secure` on every response. Even when off, they're marked on requests that
+    /// arrived over HTTPS.
+    pub secure_cookies: bool,
+    /// Believe `X-Forwarded-Proto` about whether the client used HTTPS. Only enable behind a
+    /// proxy that sets it.
+    pub trust_forwarded_proto: bool,
+    pub tls: Option<TlsConfig>,
+    pub cors_origins: Vec<String>,
+    /// Viewers allowed on a single broadcast at once.
+    pub max_viewers: usize,
+    /// How long live broadcasts get to wrap up after a shutdown signal.
+    pub drain_period: Duration,
+    /// Names this instance in the broadcast registry. Keep it stable across restarts so a
+    /// restarted instance takes its own broadcasts back straight away.
+    pub instance_id: String,
+    /// How long a controller's claim on a broadcast lasts without being renewed, which is how
+    /// long others wait to take over from an instance that died.
+    pub controller_lease: Duration,
+    pub log_format: LogFormat,
+    /// Bearer token Prometheus scrapes `/metrics` with. Metrics aren't served without one.
+    pub metrics_token: Option<String>,
+    pub oidc: Option<OidcConfig>,
+}
+
+/// Looks settings up in the environment (including `.env`), then in the TOML file.
+/// Environment variables are the upper-case form of the file's keys.
+struct Source {
+    env: HashMap<String, String>,
+    file: toml::Table,
+    errors: Vec<String>,
+}
+
+impl Source {
+    fn raw(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.env.get(&key.to_uppercase()) {
+            return Some(value.clone());
+        }
+
+        match self.file.get(key)? {
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            value => Some(value.to_string()),
+        }
+    }
+
+    fn optional(&self, key: &str) -> Option<String> {
+        self.raw(key).filter(|value| !value.trim().is_empty())
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        self.optional(key).unwrap_or_else(|| {
+            self.errors
+                .push(format!("{} must be set", key.to_uppercase()));
+            String::new()
+        })
+    }
+
+    fn parsed<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        let Some(value) = self.optional(key) else {
+            return default;
+        };
+
+        value.trim().parse().unwrap_or_else(|_| {
+            self.errors.push(format!(
+                "{} has an invalid value `{value}`",
+                key.to_uppercase()
+            ));
+            default
+        })
+    }
+
+    fn secret(&mut self, key: &str) -> String {
+        let secret = self.required(key);
+        if !secret.is_empty() && secret.len() < MIN_SECRET_LENGTH {
+            self.errors.push(format!(
+                "{} must be at least {MIN_SECRET_LENGTH} characters",
+                key.to_uppercase()
+            ));
+        }
+        secret
+    }
+}
+
+impl Config {
+    /// Loads the configuration, listing every problem found instead of stopping at the first.
+    pub fn load() -> Result<Self, String> {
+        dotenv::dotenv().ok();
+
+        let path = std::env::var("LIVESCRIPT_CONFIG").ok();
+        let file = match path.as_deref() {
+            Some(path) => Self::read_file(Path::new(path))?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::read_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => toml::Table::new(),
+        };
+
+        // Variables that aren't valid UTF-8 can't be settings, so they're skipped
+        let env = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Self::from_sources(env, file)
+    }
+
+    fn from_sources(env: HashMap<String, String>, file: toml::Table) -> Result<Self, String> {
+        let mut source = Source {
+            env,
+            file,
+            errors: Vec::new(),
+        };
+
+        let tokens = TokenConfig {
+            access_secret: source.secret("access_token_secret"),
+            refresh_secret: source.secret("refresh_token_secret"),
+            access_ttl: Duration::from_secs(source.parsed("access_token_ttl_secs", 24 * 60 * 60)),
+            refresh_ttl: Duration::from_secs(
+                source.parsed("refresh_token_ttl_secs", 14 * 24 * 60 * 60),
+            ),
+        };
+        if !tokens.access_secret.is_empty() && tokens.access_secret == tokens.refresh_secret {
+            source
+                .errors
+                .push("ACCESS_TOKEN_SECRET and REFRESH_TOKEN_SECRET must be different".to_string());
+        }
+        if tokens.access_ttl.is_zero() || tokens.refresh_ttl < tokens.access_ttl {
+            source.errors.push(
+                "Token lifetimes must be positive, with refresh tokens outliving access tokens"
+                    .to_string(),
+            );
+        }
+
+        let config = Config {
+            bind_address: source.parsed("bind_address", SocketAddr::from(([127, 0, 0, 1], 8000))),
+            database_url: source.required("db_url"),
+            bus_url: source
+                .optional("bus_url")
+                .unwrap_or_else(|| "local:".to_string()),
+            auto_migrate: source.parsed("auto_migrate", true),
+            tokens,
+            secure_cookies: source.parsed("secure_cookies", true),
+            trust_forwarded_proto: source.parsed("trust_forwarded_proto", false),
+            tls: Self::tls(&mut source),
+            cors_origins: source
+                .optional("cors_origins")
+                .map(|origins| {
+                    origins
+                        .split(',')
+                        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                        .filter(|origin| !origin.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_viewers: source.parsed("max_viewers", 10),
+            drain_period: Duration::from_secs(source.parsed("shutdown_drain_secs", 10)),
+            instance_id: source
+                .optional("instance_id")
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            controller_lease: Duration::from_secs(source.parsed("controller_lease_secs", 30)),
+            log_format: source.parsed("log_format", LogFormat::Text),
+            metrics_token: source.optional("metrics_token"),
+            oidc: Self::oidc(&mut source),
+        };
+
+        // Origins are compared as sent by browsers, so anything else would never match
+        for origin in &config.cors_origins {
+            let valid = origin.parse::<axum::http::Uri>().is_ok_and(|uri| {
+                matches!(uri.scheme_str(), Some("http" | "https"))
+                    && uri.authority().is_some()
+                    && uri.path_and_query().is_none_or(|path| path == "/")
+            });
+            if !valid {
+                source.errors.push(format!(
+                    "CORS_ORIGINS entry `{origin}` must be a scheme and host, like https://example.com"
+                ));
+            }
+        }
+
+        // Leases are renewed a few times per period, so a second would be too short to keep
+        if config.controller_lease < Duration::from_secs(3) {
+            source
+                .errors
+                .push("CONTROLLER_LEASE_SECS must be at least 3".to_string());
+        }
+
+        if config.max_viewers == 0 {
+            source
+                .errors
+                .push("MAX_VIEWERS must be at least 1".to_string());
+        }
+
+        if !source.errors.is_empty() {
+            return Err(source.errors.join("\n"));
+        }
+
+        Ok(config)
+    }
+
+    fn read_file(path: &Path) -> Result<toml::Table, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Couldn't read {}: {err}", path.display()))?;
+        contents
+            .parse()
+            .map_err(|err| format!("Couldn't parse {}: {err}", path.display()))
+    }
+
+    /// HTTPS is enabled by setting `TLS_CERT_PATH`, which then needs `TLS_KEY_PATH` too.
+    fn tls(source: &mut Source) -> Option<TlsConfig> {
+        let cert_path = source.optional("tls_cert_path")?;
+
+        Some(TlsConfig {
+            cert_path: PathBuf::from(cert_path),
+            key_path: PathBuf::from(source.required("tls_key_path")),
+            reload_interval: Duration::from_secs(source.parsed("tls_reload_secs", 60).max(1)),
+        })
+    }
+
+    /// Single sign-on is enabled by setting `OIDC_ISSUER`; the other settings are then required.
+    fn oidc(source: &mut Source) -> Option<OidcConfig> {
+        let issuer = source.optional("oidc_issuer")?;
+
+        Some(OidcConfig {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: source.required("oidc_client_id"),
+            client_secret: source.optional("oidc_client_secret"),
+            redirect_url: source.required("oidc_redirect_url"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCESS_SECRET: &str = "an-access-secret-of-at-least-32-chars";
+    const REFRESH_SECRET: &str = "a-refresh-secret-of-at-least-32-chars";
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = [
+            ("ACCESS_TOKEN_SECRET", ACCESS_SECRET),
+            ("REFRESH_TOKEN_SECRET", REFRESH_SECRET),
+            ("DB_URL", "memory:"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        for (key, value) in vars {
+            env.insert(key.to_string(), value.to_string());
+        }
+        env
+    }
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, String> {
+        Config::from_sources(env(vars), toml::Table::new())
+    }
+
+    #[test]
+    fn the_required_settings_are_enough() {
+        let config = load(&[]).unwrap();
+
+        assert_eq!(
+            config.bind_address,
+            SocketAddr::from(([127, 0, 0, 1], 8000))
+        );
+        assert_eq!(config.bus_url, "local:");
+        assert_eq!(config.tokens.access_ttl, Duration::from_secs(24 * 60 * 60));
+        assert!(config.secure_cookies);
+        assert!(config.tls.is_none());
+        assert!(config.oidc.is_none());
+    }
+
+    #[test]
+    fn every_problem_is_listed() {
+        let err = Config::from_sources(HashMap::new(), toml::Table::new()).unwrap_err();
+
+        assert!(err.contains("ACCESS_TOKEN_SECRET must be set"));
+        assert!(err.contains("REFRESH_TOKEN_SECRET must be set"));
+        assert!(err.contains("DB_URL must be set"));
+    }
+
+    #[test]
+    fn secrets_must_be_long_and_different() {
+        let err = load(&[("ACCESS_TOKEN_SECRET", "too-short")]).unwrap_err();
+        assert!(err.contains("ACCESS_TOKEN_SECRET must be at least 32 characters"));
+
+        let err = load(&[("REFRESH_TOKEN_SECRET", ACCESS_SECRET)]).unwrap_err();
+        assert!(err.contains("must be different"));
+    }
+
+    #[test]
+    fn token_lifetimes_are_checked() {
+        let err = load(&[("ACCESS_TOKEN_TTL_SECS", "0")]).unwrap_err();
+        assert!(err.contains("Token lifetimes must be positive"));
+
+        let err = load(&[
+            ("ACCESS_TOKEN_TTL_SECS", "7200"),
+            ("REFRESH_TOKEN_TTL_SECS", "3600"),
+        ])
+        .unwrap_err();
+        assert!(err.contains("refresh tokens outliving access tokens"));
+
+        let err = load(&[("ACCESS_TOKEN_TTL_SECS", "an hour")]).unwrap_err();
+        assert!(err.contains("ACCESS_TOKEN_TTL_SECS has an invalid value `an hour`"));
+    }
+
+    #[test]
+    fn cors_origins_must_be_bare_origins() {
+        let config = load(&[(
+            "CORS_ORIGINS",
+            "https://dashboard.example.com/, http://localhost:3000",
+        )])
+        .unwrap();
+        assert_eq!(
+            config.cors_origins,
+            ["https://dashboard.example.com", "http://localhost:3000"]
+        );
+
+        for origin in [
+            "dashboard.example.com",
+            "https://example.com/app",
+            "ftp://example.com",
+        ] {
+            let err = load(&[("CORS_ORIGINS", origin)]).unwrap_err();
+            assert!(
+                err.contains(&format!("CORS_ORIGINS entry `{origin}`")),
+                "{origin}"
+            );
+        }
+    }
+
+    #[test]
+    fn controller_leases_leave_room_to_renew() {
+        assert_eq!(load(&[]).unwrap().controller_lease, Duration::from_secs(30));
+
+        let err = load(&[("CONTROLLER_LEASE_SECS", "1")]).unwrap_err();
+        assert!(err.contains("CONTROLLER_LEASE_SECS must be at least 3"));
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file() {
+        let file = r#"
+            max_viewers = 5
+            log_format = "json"
+            cors_origins = ["https://a.example.com", "https://b.example.com"]
+        "#
+        .parse()
+        .unwrap();
+
+        let config = Config::from_sources(env(&[("MAX_VIEWERS", "7")]), file).unwrap();
+
+        assert_eq!(config.max_viewers, 7);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(
+            config.cors_origins,
+            ["https://a.example.com", "https://b.example.com"]
+        );
+    }
+}
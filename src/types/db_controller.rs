@@ -1,146 +1,879 @@
-use sqlx::{MySql, MySqlPool, Pool, Row};
-use std::error::Error as Std_Error;
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, error::Error as Std_Error, future::Future, time::Instant};
+use uuid::Uuid;
 
 use crate::types::{
-    auth::{Auth, UserAccessRequest},
+    api_key::{ApiKey, ApiKeyPrincipal, ApiKeyRequest, ApiKeyScope, ApiKeySecret},
+    auth::{Auth, Role, Team, User, UserAccessRequest},
+    error::AppError,
+    export::{Export, ExportedAccount, ImportSummary, EXPORT_VERSION},
     jwt::JwtManager,
+    metrics::Metrics,
+    oidc::OidcIdentity,
+    profile::{
+        AccountDeletionRequest, EmailChangeRequest, PasswordChangeRequest, Profile,
+        ProfileUpdateRequest,
+    },
+    repository::{self, Credentials, NewSession, Repository, StoredBroadcast},
+    session::{Session, SessionDevice},
+    totp::TotpManager,
     UserRegistrationRequest,
 };
 
 type Tokens = (String, String);
 
+pub enum LoginOutcome {
+    Tokens(Tokens),
+    /// The password was right, but the account also needs a TOTP code before tokens are issued.
+    TotpRequired(String),
+}
+
+/// The account rules: hashing, token minting and two-factor checks. Storage is left to
+/// whichever `Repository` the database URL selects.
 #[derive(Debug)]
 pub struct DbController {
-    pool: Pool<MySql>,
+    repo: Box<dyn Repository>,
+    jwt: JwtManager,
+    metrics: Metrics,
 }
 
 impl DbController {
-    pub async fn init() -> Result<Self, Box<dyn Std_Error>> {
-        let db_url: String = dotenv::var("DB_URL")?;
-
+    pub async fn init(
+        database_url: &str,
+        jwt: JwtManager,
+        metrics: Metrics,
+    ) -> Result<Self, Box<dyn Std_Error + Send + Sync>> {
         Ok(Self {
-            pool: MySqlPool::connect(&db_url).await?,
+            repo: repository::connect(database_url).await?,
+            jwt,
+            metrics,
         })
     }
 
-    async fn does_email_exist(&self, email: &str) -> bool {
-        return sqlx::query("SELECT id FROM auths WHERE email = ?")
-            .bind(email)
-            .fetch_one(&self.pool)
+    /// Awaits a repository call, recording how long it took under `operation`.
+    async fn timed<T>(
+        &self,
+        operation: &str,
+        call: impl Future<Output = Result<T, AppError>>,
+    ) -> Result<T, AppError> {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics.query(operation, started.elapsed());
+        result
+    }
+
+    /// Applies any migrations embedded in the binary that this database hasn't seen yet.
+    pub async fn migrate(&self) -> Result<(), AppError> {
+        self.repo.migrate().await
+    }
+
+    pub async fn baseline(&self) -> Result<(), AppError> {
+        self.repo.baseline().await
+    }
+
+    pub async fn ping(&self) -> Result<(), AppError> {
+        self.timed("ping", self.repo.ping()).await
+    }
+
+    pub async fn pending_migrations(&self) -> Result<usize, AppError> {
+        self.timed("pending_migrations", self.repo.pending_migrations())
             .await
-            .is_ok();
     }
 
-    pub async fn register(&self, request: UserRegistrationRequest) -> Result<Tokens, String> {
+    pub async fn user(&self, id: &str) -> Result<Option<User>, AppError> {
+        self.timed("user", self.repo.user(id)).await
+    }
+
+    async fn does_email_exist(&self, email: &str) -> Result<bool, AppError> {
+        Ok(self
+            .timed(
+                "credentials_by_email",
+                self.repo.credentials_by_email(email),
+            )
+            .await?
+            .is_some())
+    }
+
+    async fn credentials(&self, id: &str) -> Result<Credentials, AppError> {
+        self.timed("credentials", self.repo.credentials(id))
+            .await?
+            .ok_or(AppError::NotFound("Account"))
+    }
+
+    pub async fn register(
+        &self,
+        request: UserRegistrationRequest,
+        device: SessionDevice,
+    ) -> Result<Tokens, AppError> {
         // Check if email exists
-        if self.does_email_exist(&request.email).await {
-            return Err("User already exists".to_string());
+        if self.does_email_exist(&request.email).await? {
+            return Err(AppError::UserAlreadyExists);
+        }
+
+        if let Some(team) = &request.team {
+            self.team(team).await?;
         }
 
         // Create User representation for database
-        let (auth, access_token) = if request.team.is_none() {
-            Auth::new(request)
-        } else {
-            Auth::new_with_team(request)
+        let auth = Auth::new(request)?;
+        self.timed("insert_auth", self.repo.insert_auth(&auth))
+            .await
+            .map_err(email_taken)?;
+
+        self.create_session(&auth.id.to_string(), device).await
+    }
+
+    pub async fn login(
+        &self,
+        request: UserAccessRequest,
+        device: SessionDevice,
+    ) -> Result<LoginOutcome, AppError> {
+        let Some(user) = self
+            .timed(
+                "credentials_by_email",
+                self.repo.credentials_by_email(&request.email),
+            )
+            .await?
+        else {
+            return Err(AppError::InvalidCredentials);
+        };
+
+        if !Auth::verify_password(request.password.as_bytes(), &user.hash) {
+            return Err(AppError::InvalidCredentials);
+        }
+
+        self.first_factor_passed(&user, device).await
+    }
+
+    /// Accounts with two-factor enabled only get tokens once a code has been checked, however
+    /// they proved who they are first.
+    async fn first_factor_passed(
+        &self,
+        user: &Credentials,
+        device: SessionDevice,
+    ) -> Result<LoginOutcome, AppError> {
+        if user.totp_enabled {
+            let mfa_token = self
+                .jwt
+                .new_mfa_token(&user.id)
+                .map_err(AppError::internal)?;
+            return Ok(LoginOutcome::TotpRequired(mfa_token));
+        }
+
+        Ok(LoginOutcome::Tokens(
+            self.create_session(&user.id, device).await?,
+        ))
+    }
+
+    pub async fn login_with_totp(
+        &self,
+        id: &str,
+        code: &str,
+        device: SessionDevice,
+    ) -> Result<Tokens, AppError> {
+        if !self.verify_second_factor(id, code).await? {
+            return Err(AppError::InvalidTotpCode);
+        }
+
+        self.create_session(id, device).await
+    }
+
+    /// Signs in a user vouched for by the identity provider, linking their identity to an
+    /// existing account with the same verified email or creating a new account. Accounts with
+    /// two-factor enabled still need a code, as with a password.
+    pub async fn oidc_login(
+        &self,
+        identity: OidcIdentity,
+        device: SessionDevice,
+    ) -> Result<LoginOutcome, AppError> {
+        let linked = self
+            .timed(
+                "oidc_identity",
+                self.repo.oidc_identity(&identity.issuer, &identity.subject),
+            )
+            .await?;
+
+        let user_id = match linked {
+            Some(user_id) => user_id,
+            None => self.link_oidc_identity(identity).await?,
         };
 
-        if auth.team.is_some() {
-            if let Err(err) = sqlx::query(
-                "INSERT INTO auths (id, team, email, hash, refresh_token) VALUES (?, ?, ?, ?, ?)",
+        let Some(user) = self
+            .timed("credentials", self.repo.credentials(&user_id))
+            .await?
+        else {
+            return Err(AppError::InvalidCredentials);
+        };
+        self.first_factor_passed(&user, device).await
+    }
+
+    /// Links a new identity to the account with its verified email, creating the account if
+    /// there isn't one, and returns the account's id.
+    async fn link_oidc_identity(&self, identity: OidcIdentity) -> Result<String, AppError> {
+        let Some(email) = identity.email else {
+            return Err(AppError::Forbidden(
+                "Identity provider didn't share an email address".to_string(),
+            ));
+        };
+
+        let existing = self
+            .timed(
+                "credentials_by_email",
+                self.repo.credentials_by_email(&email),
             )
-            .bind(auth.id.to_string())
-            .bind(auth.team.unwrap().to_string())
-            .bind(auth.email)
-            .bind(auth.hash)
-            .bind(auth.refresh_token.clone())
-            .execute(&self.pool)
-            .await
-            {
-                println!("{:#?}", err);
-                return Err("Server error. Please try again".to_string());
+            .await?;
+
+        let user_id = match existing {
+            // Only trust the provider with an existing account if it verified the address
+            Some(_) if !identity.email_verified => {
+                return Err(AppError::Forbidden(
+                    "Please verify your email address with your identity provider".to_string(),
+                ));
+            }
+            Some(existing) => existing.id,
+            None => {
+                // SSO accounts get a random password nobody knows; they sign in through the provider
+                let auth = Auth::new(UserRegistrationRequest {
+                    email,
+                    password: Uuid::new_v4().to_string(),
+                    team: None,
+                    device: None,
+                })?;
+                self.timed("insert_auth", self.repo.insert_auth(&auth))
+                    .await?;
+
+                auth.id.to_string()
             }
-        } else {
-            if let Err(err) = sqlx::query(
-                "INSERT INTO auths (id, email, hash, refresh_token) VALUES (?, ?, ?, ?)",
+        };
+
+        self.timed(
+            "link_oidc_identity",
+            self.repo
+                .link_oidc_identity(&identity.issuer, &identity.subject, &user_id),
+        )
+        .await?;
+
+        Ok(user_id)
+    }
+
+    /// Opens a new session for the user, giving the device its own refresh token.
+    async fn create_session(
+        &self,
+        user_id: &str,
+        device: SessionDevice,
+    ) -> Result<Tokens, AppError> {
+        if self.user(user_id).await?.is_some_and(|user| user.disabled) {
+            return Err(AppError::AccountDisabled);
+        }
+
+        let session_id = Uuid::new_v4().to_string();
+
+        let access_token = self
+            .jwt
+            .new_access_token(user_id, &session_id)
+            .map_err(AppError::internal)?;
+        let refresh_token = self
+            .jwt
+            .new_refresh_token(user_id, &session_id)
+            .map_err(AppError::internal)?;
+
+        let session = NewSession {
+            id: &session_id,
+            auth: user_id,
+            refresh_token: &refresh_token,
+            device,
+        };
+        self.timed("insert_session", self.repo.insert_session(session))
+            .await?;
+
+        Ok((access_token, refresh_token))
+    }
+
+    pub async fn logout(&self, id: &str, session_id: &str) -> Result<(), AppError> {
+        self.timed("delete_session", self.repo.delete_session(session_id, id))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn refresh(
+        &self,
+        id: &str,
+        session_id: &str,
+        refresh_token: &str,
+    ) -> Result<String, AppError> {
+        // The refresh token must belong to a session that hasn't been revoked
+        if !self
+            .timed(
+                "touch_session",
+                self.repo.touch_session(session_id, id, refresh_token),
             )
-            .bind(auth.id.to_string())
-            .bind(auth.email)
-            .bind(auth.hash)
-            .bind(auth.refresh_token.clone())
-            .execute(&self.pool)
+            .await?
+        {
+            return Err(AppError::SessionExpired);
+        }
+
+        self.jwt
+            .new_access_token(id, session_id)
+            .map_err(AppError::internal)
+    }
+
+    pub async fn sessions(&self, id: &str) -> Result<Vec<Session>, AppError> {
+        self.timed("sessions", self.repo.sessions(id)).await
+    }
+
+    /// Whether the session an access token was issued for is still open.
+    pub async fn session_exists(&self, id: &str, session_id: &str) -> Result<bool, AppError> {
+        self.timed("session_exists", self.repo.session_exists(session_id, id))
             .await
-            {
-                println!("{:#?}", err);
-                return Err("Server error. Please try again".to_string());
-            };
+    }
+
+    pub async fn revoke_session(&self, id: &str, session_id: &str) -> Result<(), AppError> {
+        if !self
+            .timed("delete_session", self.repo.delete_session(session_id, id))
+            .await?
+        {
+            return Err(AppError::NotFound("Session"));
         }
-        Ok((access_token, auth.refresh_token))
+
+        Ok(())
     }
 
-    pub async fn login(&self, request: UserAccessRequest) -> Result<Tokens, String> {
-        if let Ok(user) = sqlx::query("SELECT * FROM auths WHERE email = ?")
-            .bind(&request.email)
-            .fetch_one(&self.pool)
+    pub async fn revoke_all_sessions(&self, id: &str) -> Result<(), AppError> {
+        self.timed("delete_sessions", self.repo.delete_sessions(id, None))
             .await
+    }
+
+    pub async fn revoke_token(&self, jti: &str, exp: usize) -> Result<(), AppError> {
+        let Some(expires_at) = DateTime::<Utc>::from_timestamp(exp as i64, 0) else {
+            return Err(AppError::InvalidToken);
+        };
+
+        self.timed(
+            "insert_revoked_token",
+            self.repo.insert_revoked_token(jti, expires_at),
+        )
+        .await
+    }
+
+    /// Loads every revocation that still refers to an unexpired token, clearing out the rest.
+    pub async fn revoked_tokens(&self) -> Result<HashMap<String, usize>, AppError> {
+        Ok(self
+            .timed("revoked_tokens", self.repo.revoked_tokens())
+            .await?
+            .into_iter()
+            .map(|(jti, expires_at)| (jti, expires_at.timestamp() as usize))
+            .collect())
+    }
+
+    pub async fn create_api_key(
+        &self,
+        team: &Uuid,
+        created_by: &Uuid,
+        request: ApiKeyRequest,
+    ) -> Result<(ApiKey, String), AppError> {
+        let secret = ApiKeySecret::generate()?;
+
+        let api_key = ApiKey {
+            id: secret.id,
+            name: request.name,
+            scopes: request.scopes,
+            created_at: Utc::now(),
+            last_used: None,
+        };
+
+        self.timed(
+            "insert_api_key",
+            self.repo
+                .insert_api_key(&api_key, team, &secret.hash, created_by),
+        )
+        .await?;
+
+        Ok((api_key, secret.token))
+    }
+
+    pub async fn api_keys(&self, team: &Uuid) -> Result<Vec<ApiKey>, AppError> {
+        self.timed("api_keys", self.repo.api_keys(team)).await
+    }
+
+    pub async fn revoke_api_key(&self, team: &Uuid, id: &str) -> Result<(), AppError> {
+        if !self
+            .timed("delete_api_key", self.repo.delete_api_key(team, id))
+            .await?
         {
-            let user_id: &str = user.get("id");
-            let hash: &str = user.get("hash");
+            return Err(AppError::NotFound("API key"));
+        }
 
-            // If it gets to this point, there's guaranteed to be a hash value returned from the user row.
-            let is_auth_valid = Auth::verify_password(request.password.as_bytes(), hash);
+        Ok(())
+    }
 
-            if !is_auth_valid {
-                return Err("Please enter a valid email or password".to_string());
-            }
+    /// Checks a presented API key against its stored hash, returning the team and scopes it grants.
+    pub async fn verify_api_key(&self, token: &str) -> Result<Option<ApiKeyPrincipal>, AppError> {
+        let Some((id, secret)) = ApiKeySecret::parse(token) else {
+            return Ok(None);
+        };
 
-            let access_token = JwtManager::new_access_token(user_id).unwrap();
-            let refresh_token = JwtManager::new_refresh_token(user_id).unwrap();
-
-            if sqlx::query("UPDATE auths SET refresh_token = ? WHERE email = ?")
-                .bind(&refresh_token)
-                .bind(&request.email)
-                .execute(&self.pool)
-                .await
-                .is_err()
-            {
-                return Err("There seems to be a server error. Please try again".to_string());
-            }
+        let Some(stored) = self
+            .timed("stored_api_key", self.repo.stored_api_key(&id))
+            .await?
+        else {
+            return Ok(None);
+        };
 
-            return Ok((access_token, refresh_token));
-        } else {
-            return Err("Please enter a valid email or password".to_string());
+        if !ApiKeySecret::verify(secret, &stored.hash) {
+            return Ok(None);
         }
+
+        let _ = self
+            .timed("touch_api_key", self.repo.touch_api_key(&id))
+            .await;
+
+        Ok(Some(ApiKeyPrincipal {
+            id,
+            team: stored.team,
+            scopes: ApiKeyScope::split(&stored.scopes),
+        }))
     }
 
-    pub async fn logout(&self, id: &str) -> Result<(), String> {
-        if sqlx::query("UPDATE auths SET refresh_token = ? WHERE id = ?")
-            .bind("")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .is_err()
+    /// Stores a fresh, unconfirmed secret. Two-factor stays off until a code from it is confirmed.
+    pub async fn begin_totp_enrollment(&self, user: &User) -> Result<(String, String), AppError> {
+        let id = user.id.to_string();
+
+        if self.credentials(&id).await?.totp_enabled {
+            return Err(AppError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = TotpManager::generate_secret();
+        let Some(provisioning_uri) = TotpManager::provisioning_uri(&secret, &user.email) else {
+            return Err(AppError::internal("couldn't build TOTP provisioning URI"));
+        };
+
+        self.timed("set_totp_secret", self.repo.set_totp_secret(&id, &secret))
+            .await?;
+
+        Ok((secret, provisioning_uri))
+    }
+
+    /// Enables two-factor once the user proves their authenticator works, returning recovery codes.
+    pub async fn confirm_totp(&self, id: &str, code: &str) -> Result<Vec<String>, AppError> {
+        let credentials = self.credentials(id).await?;
+
+        if credentials.totp_enabled {
+            return Err(AppError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let Some(secret) = credentials.totp_secret else {
+            return Err(AppError::Conflict(
+                "Two-factor enrollment hasn't been started".to_string(),
+            ));
+        };
+
+        // Burned like any other code, so it can't sign in again straight away
+        let Some(step) = TotpManager::verify(&secret, code) else {
+            return Err(AppError::InvalidTotpCode);
+        };
+        if !self
+            .timed("use_totp_step", self.repo.use_totp_step(id, step))
+            .await?
         {
-            return Err("Error logging out. Please try again".to_string());
+            return Err(AppError::InvalidTotpCode);
         }
 
-        Ok(())
+        let (codes, hashes): (Vec<_>, Vec<_>) =
+            TotpManager::generate_recovery_codes()?.into_iter().unzip();
+
+        self.timed("enable_totp", self.repo.enable_totp(id, &hashes))
+            .await?;
+
+        Ok(codes)
     }
 
-    pub async fn refresh(&self, id: &str) -> Result<String, String> {
-        let Ok(user) = sqlx::query("SELECT id FROM auths WHERE id = ?")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await
+    /// Accepts either a current TOTP code or an unused recovery code, burning either.
+    async fn verify_second_factor(&self, id: &str, code: &str) -> Result<bool, AppError> {
+        let Some(secret) = self
+            .timed("credentials", self.repo.credentials(id))
+            .await?
+            .filter(|credentials| credentials.totp_enabled)
+            .and_then(|credentials| credentials.totp_secret)
         else {
-            return Err("Forbidden".to_string());
+            return Ok(false);
         };
 
-        let user_id: &str = user.get("id");
+        // A code is only good once, so one that's overheard can't be replayed while still current
+        if let Some(step) = TotpManager::verify(&secret, code) {
+            return self
+                .timed("use_totp_step", self.repo.use_totp_step(id, step))
+                .await;
+        }
+
+        let recovery_codes = self
+            .timed("unused_recovery_codes", self.repo.unused_recovery_codes(id))
+            .await?;
+
+        let Some((recovery_code, _)) = recovery_codes
+            .iter()
+            .find(|(_, hash)| TotpManager::verify_recovery_code(code, hash))
+        else {
+            return Ok(false);
+        };
+
+        self.timed(
+            "use_recovery_code",
+            self.repo.use_recovery_code(recovery_code),
+        )
+        .await
+    }
+
+    pub async fn profile(&self, id: &str) -> Result<Profile, AppError> {
+        self.timed("profile", self.repo.profile(id))
+            .await?
+            .ok_or(AppError::NotFound("Account"))
+    }
+
+    pub async fn update_profile(
+        &self,
+        id: &str,
+        request: ProfileUpdateRequest,
+    ) -> Result<Profile, AppError> {
+        self.timed("update_profile", self.repo.update_profile(id, &request))
+            .await?;
+        self.profile(id).await
+    }
+
+    async fn verify_user_password(&self, id: &str, password: &str) -> Result<(), AppError> {
+        let credentials = self.credentials(id).await?;
+
+        if !Auth::verify_password(password.as_bytes(), &credentials.hash) {
+            return Err(AppError::IncorrectPassword);
+        }
+
+        Ok(())
+    }
+
+    pub async fn change_email(
+        &self,
+        id: &str,
+        request: EmailChangeRequest,
+    ) -> Result<(), AppError> {
+        self.verify_user_password(id, &request.password).await?;
+
+        if self.does_email_exist(&request.email).await? {
+            return Err(AppError::UserAlreadyExists);
+        }
+
+        self.timed("update_email", self.repo.update_email(id, &request.email))
+            .await
+            .map_err(email_taken)
+    }
+
+    /// Changes the password and signs out every other device, keeping the current session.
+    pub async fn change_password(
+        &self,
+        id: &str,
+        session_id: &str,
+        request: PasswordChangeRequest,
+    ) -> Result<(), AppError> {
+        self.verify_user_password(id, &request.current_password)
+            .await?;
+
+        let hash = Auth::hash_password(&request.new_password)?;
+
+        self.timed("update_hash", self.repo.update_hash(id, &hash))
+            .await?;
+        self.timed(
+            "delete_sessions",
+            self.repo.delete_sessions(id, Some(session_id)),
+        )
+        .await
+    }
+
+    pub async fn delete_account(
+        &self,
+        id: &str,
+        request: AccountDeletionRequest,
+    ) -> Result<(), AppError> {
+        self.verify_user_password(id, &request.password).await?;
+
+        // Sessions, recovery codes and linked identities go with it
+        self.timed("delete_auth", self.repo.delete_auth(id)).await
+    }
+
+    pub async fn save_broadcast(&self, broadcast: &StoredBroadcast) -> Result<(), AppError> {
+        self.timed("save_broadcast", self.repo.save_broadcast(broadcast))
+            .await
+    }
+
+    pub async fn delete_broadcast(&self, id: &Uuid) -> Result<(), AppError> {
+        self.timed("delete_broadcast", self.repo.delete_broadcast(id))
+            .await
+    }
+
+    pub async fn broadcast(&self, id: &Uuid) -> Result<Option<StoredBroadcast>, AppError> {
+        self.timed("broadcast", self.repo.broadcast(id)).await
+    }
+
+    pub async fn broadcasts(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<StoredBroadcast>, AppError> {
+        self.timed("broadcasts", self.repo.broadcasts(cutoff)).await
+    }
+
+    pub async fn claim_broadcast(
+        &self,
+        id: &Uuid,
+        instance: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        self.timed(
+            "claim_broadcast",
+            self.repo.claim_broadcast(id, instance, expires_at),
+        )
+        .await
+    }
+
+    pub async fn release_broadcast(&self, id: &Uuid, instance: &str) -> Result<(), AppError> {
+        self.timed(
+            "release_broadcast",
+            self.repo.release_broadcast(id, instance),
+        )
+        .await
+    }
+
+    pub async fn users(&self) -> Result<Vec<User>, AppError> {
+        self.timed("users", self.repo.users()).await
+    }
+
+    /// Looks an account up by id or, failing that, by email.
+    pub async fn find_user(&self, id_or_email: &str) -> Result<User, AppError> {
+        if let Some(user) = self.user(id_or_email).await? {
+            return Ok(user);
+        }
+
+        let credentials = self
+            .timed(
+                "credentials_by_email",
+                self.repo.credentials_by_email(id_or_email),
+            )
+            .await?
+            .ok_or(AppError::NotFound("Account"))?;
+        self.user(&credentials.id)
+            .await?
+            .ok_or(AppError::NotFound("Account"))
+    }
+
+    /// Creates an account on an operator's behalf, without signing it in anywhere.
+    pub async fn create_user(
+        &self,
+        email: &str,
+        password: &str,
+        team: Option<Uuid>,
+        role: Role,
+    ) -> Result<User, AppError> {
+        if !Auth::validate_email(email) || !Auth::validate_password(password) {
+            return Err(AppError::Validation(
+                "Please enter a valid email or password".to_string(),
+            ));
+        }
+        if self.does_email_exist(email).await? {
+            return Err(AppError::UserAlreadyExists);
+        }
+        if let Some(team) = team {
+            self.team(&team).await?;
+        }
+
+        let auth = Auth::new(UserRegistrationRequest {
+            email: email.to_string(),
+            password: password.to_string(),
+            team,
+            device: None,
+        })?;
+        let id = auth.id.to_string();
+        self.timed("insert_auth", self.repo.insert_auth(&auth))
+            .await
+            .map_err(email_taken)?;
+        if role != Role::default() {
+            self.timed("update_role", self.repo.update_role(&id, role))
+                .await?;
+        }
+
+        self.find_user(&id).await
+    }
+
+    /// Sets a new password and signs the account out everywhere.
+    pub async fn reset_password(&self, id: &str, password: &str) -> Result<(), AppError> {
+        if !Auth::validate_password(password) {
+            return Err(AppError::Validation(
+                "Please enter a valid password".to_string(),
+            ));
+        }
+        self.credentials(id).await?;
+
+        let hash = Auth::hash_password(password)?;
+        self.timed("update_hash", self.repo.update_hash(id, &hash))
+            .await?;
+        self.revoke_all_sessions(id).await
+    }
+
+    /// Disabling also signs the account out everywhere, so only a new login could restore access.
+    pub async fn set_disabled(&self, id: &str, disabled: bool) -> Result<(), AppError> {
+        self.credentials(id).await?;
+        self.timed("set_disabled", self.repo.set_disabled(id, disabled))
+            .await?;
+        if disabled {
+            self.revoke_all_sessions(id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn teams(&self) -> Result<Vec<Team>, AppError> {
+        self.timed("teams", self.repo.teams()).await
+    }
+
+    async fn team(&self, id: &Uuid) -> Result<Team, AppError> {
+        self.teams()
+            .await?
+            .into_iter()
+            .find(|team| team.id == *id)
+            .ok_or_else(|| AppError::Validation("Team not found".to_string()))
+    }
+
+    pub async fn create_team(&self, name: &str) -> Result<Team, AppError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::Validation("Team name is required".to_string()));
+        }
 
-        let Ok(access_token) = JwtManager::new_access_token(user_id) else {
-            return Err("Server Error. Please try again.".to_string());
+        let team = Team {
+            id: Uuid::new_v4(),
+            name: Some(name.to_string()),
         };
+        self.timed("insert_team", self.repo.insert_team(&team))
+            .await?;
+        Ok(team)
+    }
+
+    pub async fn export(&self) -> Result<Export, AppError> {
+        let mut accounts = Vec::new();
+        for user in self.users().await? {
+            let id = user.id.to_string();
+            let credentials = self.credentials(&id).await?;
+            let profile = self.profile(&id).await?;
+            let recovery_codes = if credentials.totp_enabled {
+                self.timed(
+                    "unused_recovery_codes",
+                    self.repo.unused_recovery_codes(&id),
+                )
+                .await?
+                .into_iter()
+                .map(|(_, hash)| hash)
+                .collect()
+            } else {
+                Vec::new()
+            };
+
+            accounts.push(ExportedAccount {
+                id: user.id,
+                email: user.email,
+                team: user.team.map(|team| team.id),
+                role: user.role,
+                disabled: user.disabled,
+                hash: credentials.hash,
+                totp_secret: credentials.totp_secret.filter(|_| credentials.totp_enabled),
+                recovery_codes,
+                display_name: profile.display_name,
+                scroll_speed: profile.scroll_speed,
+                font_size: profile.font_size,
+                mirror_mode: profile.mirror_mode,
+            });
+        }
+
+        Ok(Export {
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            teams: self.teams().await?,
+            accounts,
+        })
+    }
+
+    /// Adds whatever in `export` isn't here yet. Teams go first so accounts can join them.
+    pub async fn import(&self, export: Export) -> Result<ImportSummary, AppError> {
+        if export.version > EXPORT_VERSION {
+            return Err(AppError::Validation(format!(
+                "Export version {} is newer than this server understands",
+                export.version
+            )));
+        }
+
+        let mut summary = ImportSummary::default();
+        let existing: Vec<Uuid> = self.teams().await?.iter().map(|team| team.id).collect();
+        for team in export.teams {
+            if existing.contains(&team.id) {
+                summary.skipped.push(format!("team {}", team.id));
+                continue;
+            }
+            self.timed("insert_team", self.repo.insert_team(&team))
+                .await?;
+            summary.teams += 1;
+        }
+
+        for account in export.accounts {
+            let id = account.id.to_string();
+            if self.user(&id).await?.is_some() || self.does_email_exist(&account.email).await? {
+                summary.skipped.push(account.email);
+                continue;
+            }
+
+            let auth = Auth {
+                id: account.id,
+                team: account.team,
+                email: account.email,
+                hash: account.hash,
+            };
+            self.timed("insert_auth", self.repo.insert_auth(&auth))
+                .await?;
+            self.timed("update_role", self.repo.update_role(&id, account.role))
+                .await?;
+            if account.disabled {
+                self.timed("set_disabled", self.repo.set_disabled(&id, true))
+                    .await?;
+            }
+            self.timed(
+                "update_profile",
+                self.repo.update_profile(
+                    &id,
+                    &ProfileUpdateRequest {
+                        display_name: account.display_name,
+                        scroll_speed: Some(account.scroll_speed),
+                        font_size: Some(account.font_size),
+                        mirror_mode: Some(account.mirror_mode),
+                    },
+                ),
+            )
+            .await?;
+            if let Some(secret) = &account.totp_secret {
+                self.timed("set_totp_secret", self.repo.set_totp_secret(&id, secret))
+                    .await?;
+                self.timed(
+                    "enable_totp",
+                    self.repo.enable_totp(&id, &account.recovery_codes),
+                )
+                .await?;
+            }
+            summary.accounts += 1;
+        }
+
+        Ok(summary)
+    }
+}
 
-        Ok(access_token)
+/// Two requests can both pass the email check before either is stored; the loser sees this.
+fn email_taken(err: AppError) -> AppError {
+    match err {
+        AppError::Conflict(_) => AppError::UserAlreadyExists,
+        err => err,
     }
 }
@@ -0,0 +1,192 @@
+use std::{fmt, time::Duration};
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+/// Every failure the API reports. Each variant maps to one status and a stable `code`
+/// clients can match on; `message` is for people and may change.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request body or parameters failed validation.
+    Validation(String),
+    /// The body, path or query couldn't be read at all; keeps the status axum chose for it.
+    Malformed(StatusCode, String),
+    InvalidCredentials,
+    IncorrectPassword,
+    MissingToken,
+    InvalidToken,
+    RevokedToken,
+    /// A refresh token or login challenge no longer refers to a live session.
+    SessionExpired,
+    InvalidTotpCode,
+    AccountDisabled,
+    /// A cookie-authenticated request that changes something didn't echo the CSRF cookie.
+    InvalidCsrfToken,
+    Forbidden(String),
+    MissingScope,
+    NotFound(&'static str),
+    UserAlreadyExists,
+    Conflict(String),
+    RateLimited(Duration),
+    /// The server is draining before a restart; retry once it's back.
+    ShuttingDown(Duration),
+    /// A service we depend on, such as the identity provider, failed.
+    Upstream(String),
+    /// Anything the client can't fix. The cause is logged, never sent.
+    Internal(String),
+}
+
+impl AppError {
+    pub fn internal(err: impl fmt::Debug) -> Self {
+        AppError::Internal(format!("{err:?}"))
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Malformed(status, _) => *status,
+            AppError::InvalidCredentials
+            | AppError::IncorrectPassword
+            | AppError::MissingToken
+            | AppError::InvalidToken
+            | AppError::RevokedToken
+            | AppError::SessionExpired
+            | AppError::InvalidTotpCode => StatusCode::UNAUTHORIZED,
+            AppError::AccountDisabled
+            | AppError::InvalidCsrfToken
+            | AppError::Forbidden(_)
+            | AppError::MissingScope => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::UserAlreadyExists | AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::ShuttingDown(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "validation_failed",
+            AppError::Malformed(..) => "malformed_request",
+            AppError::InvalidCredentials => "invalid_credentials",
+            AppError::IncorrectPassword => "incorrect_password",
+            AppError::MissingToken => "missing_token",
+            AppError::InvalidToken => "invalid_token",
+            AppError::RevokedToken => "revoked_token",
+            AppError::SessionExpired => "session_expired",
+            AppError::InvalidTotpCode => "invalid_totp_code",
+            AppError::AccountDisabled => "account_disabled",
+            AppError::InvalidCsrfToken => "invalid_csrf_token",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::MissingScope => "missing_scope",
+            AppError::NotFound(_) => "not_found",
+            AppError::UserAlreadyExists => "user_exists",
+            AppError::Conflict(_) => "conflict",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::ShuttingDown(_) => "shutting_down",
+            AppError::Upstream(_) => "upstream_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AppError::Validation(message)
+            | AppError::Malformed(_, message)
+            | AppError::Forbidden(message)
+            | AppError::Conflict(message)
+            | AppError::Upstream(message) => message.clone(),
+            AppError::InvalidCredentials => "Please enter a valid email or password".to_string(),
+            AppError::IncorrectPassword => "Incorrect password".to_string(),
+            AppError::MissingToken => "Please log in".to_string(),
+            AppError::InvalidToken => "Invalid token".to_string(),
+            AppError::RevokedToken => "Token has been revoked".to_string(),
+            AppError::SessionExpired => "Session expired. Please log in again".to_string(),
+            AppError::InvalidTotpCode => "Invalid authentication code".to_string(),
+            AppError::AccountDisabled => "This account has been disabled".to_string(),
+            AppError::InvalidCsrfToken => "Missing or invalid CSRF token".to_string(),
+            AppError::MissingScope => "API key is not allowed to do that".to_string(),
+            AppError::NotFound(what) => format!("{what} not found"),
+            AppError::UserAlreadyExists => "User already exists".to_string(),
+            AppError::RateLimited(_) => "Too many attempts. Please try again later".to_string(),
+            AppError::ShuttingDown(_) => {
+                "Server is restarting. Please try again shortly".to_string()
+            }
+            AppError::Internal(_) => "Server error. Please try again".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Internal(cause) => write!(f, "internal error: {cause}"),
+            _ => f.write_str(&self.message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Constraint violations are the client's doing, e.g. two registrations racing for one email.
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                AppError::Conflict("That already exists".to_string())
+            }
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                AppError::Validation("That refers to something that doesn't exist".to_string())
+            }
+            _ => AppError::internal(err),
+        }
+    }
+}
+
+macro_rules! from_rejection {
+    ($($rejection:ty),*) => {$(
+        impl From<$rejection> for AppError {
+            fn from(rejection: $rejection) -> Self {
+                AppError::Malformed(rejection.status(), rejection.body_text())
+            }
+        }
+    )*};
+}
+
+from_rejection!(JsonRejection, PathRejection, QueryRejection);
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    success: bool,
+    code: &'static str,
+    message: String,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if let AppError::Internal(cause) = &self {
+            tracing::error!(%cause, "Internal error");
+        }
+
+        let body = Json(ErrorResponse {
+            success: false,
+            code: self.code(),
+            message: self.message(),
+        });
+
+        match self {
+            AppError::RateLimited(retry_after) | AppError::ShuttingDown(retry_after) => {
+                // Round up so clients never retry a moment too early
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                (self.status(), [(RETRY_AFTER, seconds.to_string())], body).into_response()
+            }
+            _ => (self.status(), body).into_response(),
+        }
+    }
+}
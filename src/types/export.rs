@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::{auth::Role, auth::Team, profile::MirrorMode};
+
+/// Bumped whenever an older server couldn't read what a newer one exports.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Teams and accounts as written by `livescript-admin export`, enough to rebuild them on a
+/// fresh database of any backend. Sessions, API keys and broadcasts aren't included.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub teams: Vec<Team>,
+    pub accounts: Vec<ExportedAccount>,
+}
+
+/// An account with its password hash, so people keep signing in the way they did.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedAccount {
+    pub id: Uuid,
+    pub email: String,
+    pub team: Option<Uuid>,
+    pub role: Role,
+    #[serde(default)]
+    pub disabled: bool,
+    pub hash: String,
+    /// Only set when two-factor is enabled, along with the hashes of unused recovery codes.
+    pub totp_secret: Option<String>,
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
+    pub display_name: Option<String>,
+    pub scroll_speed: i16,
+    pub font_size: i16,
+    pub mirror_mode: MirrorMode,
+}
+
+/// What an import did. Anything whose id or email is already taken is left alone.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub teams: usize,
+    pub accounts: usize,
+    pub skipped: Vec<String>,
+}
@@ -1,16 +1,24 @@
-use std::error::Error;
-
-use chrono::{Days, Local};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, errors::Result, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::{config::TokenConfig, oidc::PendingLogin};
+
+/// How long a user has after their password to enter a TOTP code.
+const MFA_TOKEN_TTL: usize = 5 * 60;
+/// How long a user has to finish signing in at the identity provider.
+const OIDC_STATE_TTL: usize = 10 * 60;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AccessTokenClaims {
     aud: String,
-    exp: usize,
+    pub exp: usize,
     iat: usize,
     iss: String,
     pub sub: String,
+    pub sid: String,
+    pub jti: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,69 +28,149 @@ pub struct RefreshTokenClaims {
     iat: usize,
     iss: String,
     pub sub: String,
+    pub sid: String,
+}
+
+/// Proves the password step of a login succeeded while a TOTP code is still outstanding.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaTokenClaims {
+    aud: String,
+    pub exp: usize,
+    iat: usize,
+    iss: String,
+    pub sub: String,
+    pub jti: String,
+}
+
+/// A single sign-on login in progress, kept in the browser that started it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcStateClaims {
+    aud: String,
+    exp: usize,
+    iat: usize,
+    iss: String,
+    #[serde(flatten)]
+    pub login: PendingLogin,
 }
 
-pub struct JwtManager;
+/// Issues and checks the server's tokens with the configured secrets and lifetimes.
+#[derive(Debug, Clone)]
+pub struct JwtManager {
+    config: TokenConfig,
+}
 
 impl JwtManager {
-    pub fn new_access_token(id: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+    pub fn new(config: TokenConfig) -> Self {
+        Self { config }
+    }
+
+    fn now() -> usize {
+        Utc::now().timestamp() as usize
+    }
+
+    pub fn new_access_token(&self, id: &str, session_id: &str) -> Result<String> {
         let claims: AccessTokenClaims = AccessTokenClaims {
             aud: String::from("livescript.app"),
-            exp: Local::now()
-                .checked_add_days(Days::new(1))
-                .unwrap()
-                .timestamp() as usize,
-            iat: chrono::offset::Utc::now().timestamp() as usize,
+            exp: Self::now() + self.config.access_ttl.as_secs() as usize,
+            iat: Self::now(),
             iss: String::from("livescript.app/auth"),
             sub: id.to_string(),
+            sid: session_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
         };
-        let token = encode(
+        encode(
             &Header::default(),
             &claims,
-            &EncodingKey::from_secret(dotenv::var("ACCESS_TOKEN_SECRET")?.as_ref()),
-        )?;
-        Ok(token)
+            &EncodingKey::from_secret(self.config.access_secret.as_ref()),
+        )
     }
-    pub fn new_refresh_token(id: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+
+    pub fn new_refresh_token(&self, id: &str, session_id: &str) -> Result<String> {
         let claims: RefreshTokenClaims = RefreshTokenClaims {
             aud: String::from("livescript.app"),
-            exp: Local::now()
-                .checked_add_days(Days::new(14))
-                .unwrap()
-                .timestamp() as usize,
-            iat: chrono::offset::Utc::now().timestamp() as usize,
+            exp: Self::now() + self.config.refresh_ttl.as_secs() as usize,
+            iat: Self::now(),
             iss: String::from("livescript.app/auth"),
             sub: id.to_string(),
+            sid: session_id.to_string(),
         };
-        let token = encode(
+        encode(
             &Header::default(),
             &claims,
-            &EncodingKey::from_secret(dotenv::var("REFRESH_TOKEN_SECRET")?.as_ref()),
-        )?;
-        Ok(token)
+            &EncodingKey::from_secret(self.config.refresh_secret.as_ref()),
+        )
     }
 
-    pub fn decode_access_token(
-        encoded_token: &str,
-    ) -> Result<AccessTokenClaims, Box<dyn Error + Sync + Send>> {
+    pub fn decode_access_token(&self, encoded_token: &str) -> Result<AccessTokenClaims> {
         let mut validation = Validation::new(jsonwebtoken::Algorithm::HS256);
         validation.set_audience(&["livescript.app"]);
         Ok(decode::<AccessTokenClaims>(
             encoded_token,
-            &DecodingKey::from_secret(dotenv::var("ACCESS_TOKEN_SECRET")?.as_ref()),
+            &DecodingKey::from_secret(self.config.access_secret.as_ref()),
             &validation,
         )?
         .claims)
     }
 
-    pub fn decode_refresh_token(
-        encoded_token: &str,
-    ) -> Result<RefreshTokenClaims, Box<dyn Error + Sync + Send>> {
+    pub fn decode_refresh_token(&self, encoded_token: &str) -> Result<RefreshTokenClaims> {
         let mut validation = Validation::new(jsonwebtoken::Algorithm::HS256);
         validation.set_audience(&["livescript.app"]);
         Ok(decode::<RefreshTokenClaims>(
             encoded_token,
-            &DecodingKey::from_secret(dotenv::var("REFRESH_TOKEN_SECRET")?.as_ref()),
+            &DecodingKey::from_secret(self.config.refresh_secret.as_ref()),
+            &validation,
+        )?
+        .claims)
+    }
+
+    pub fn new_mfa_token(&self, id: &str) -> Result<String> {
+        let claims = MfaTokenClaims {
+            aud: String::from("livescript.app/mfa"),
+            exp: Self::now() + MFA_TOKEN_TTL,
+            iat: Self::now(),
+            iss: String::from("livescript.app/auth"),
+            sub: id.to_string(),
+            jti: Uuid::new_v4().to_string(),
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.config.access_secret.as_ref()),
+        )
+    }
+
+    pub fn decode_mfa_token(&self, encoded_token: &str) -> Result<MfaTokenClaims> {
+        let mut validation = Validation::new(jsonwebtoken::Algorithm::HS256);
+        validation.set_audience(&["livescript.app/mfa"]);
+        Ok(decode::<MfaTokenClaims>(
+            encoded_token,
+            &DecodingKey::from_secret(self.config.access_secret.as_ref()),
+            &validation,
+        )?
+        .claims)
+    }
+
+    pub fn new_oidc_state_token(&self, login: PendingLogin) -> Result<String> {
+        let claims = OidcStateClaims {
+            aud: String::from("livescript.app/oidc"),
+            exp: Self::now() + OIDC_STATE_TTL,
+            iat: Self::now(),
+            iss: String::from("livescript.app/auth"),
+            login,
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.config.access_secret.as_ref()),
+        )
+    }
+
+    pub fn decode_oidc_state_token(&self, encoded_token: &str) -> Result<OidcStateClaims> {
+        let mut validation = Validation::new(jsonwebtoken::Algorithm::HS256);
+        validation.set_audience(&["livescript.app/oidc"]);
+        Ok(decode::<OidcStateClaims>(
+            encoded_token,
+            &DecodingKey::from_secret(self.config.access_secret.as_ref()),
             &validation,
         )?
         .claims)
@@ -0,0 +1,133 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use prometheus::{
+    histogram_opts, opts, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Registry, TextEncoder,
+};
+use uuid::Uuid;
+
+use super::Broadcast;
+
+/// Counters and gauges served at `/metrics`. Each server has its own registry, so several can
+/// run in one process without sharing numbers.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    live_broadcasts: IntGauge,
+    subscribers: IntGaugeVec,
+    commands: IntCounterVec,
+    lagged_messages: IntCounter,
+    logins: IntCounterVec,
+    query_seconds: HistogramVec,
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("livescript".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let live_broadcasts =
+            IntGauge::with_opts(opts!("live_broadcasts", "Broadcasts live on this instance"))
+                .expect("valid metric");
+        let subscribers = IntGaugeVec::new(
+            opts!(
+                "broadcast_subscribers",
+                "Viewers connected to each broadcast"
+            ),
+            &["broadcast"],
+        )
+        .expect("valid metric");
+        let commands = IntCounterVec::new(
+            opts!("commands_relayed_total", "Controller commands by type"),
+            &["command"],
+        )
+        .expect("valid metric");
+        let lagged_messages = IntCounter::with_opts(opts!(
+            "lagged_messages_total",
+            "Messages missed by clients too slow to keep up, which are then disconnected"
+        ))
+        .expect("valid metric");
+        let logins = IntCounterVec::new(
+            opts!("logins_total", "Login attempts by method and outcome"),
+            &["method", "outcome"],
+        )
+        .expect("valid metric");
+        let query_seconds = HistogramVec::new(
+            histogram_opts!("db_query_duration_seconds", "Database calls by operation"),
+            &["operation"],
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(live_broadcasts.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(subscribers.clone()),
+            Box::new(commands.clone()),
+            Box::new(lagged_messages.clone()),
+            Box::new(logins.clone()),
+            Box::new(query_seconds.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric registered once");
+        }
+
+        Self {
+            registry,
+            live_broadcasts,
+            subscribers,
+            commands,
+            lagged_messages,
+            logins,
+            query_seconds,
+        }
+    }
+
+    /// `command` is the command's type, or `invalid` for one that was rejected.
+    pub fn command(&self, command: &str) {
+        self.commands.with_label_values(&[command]).inc();
+    }
+
+    pub fn lagged(&self, messages: u64) {
+        self.lagged_messages.inc_by(messages);
+    }
+
+    pub fn login(&self, method: &str, success: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        self.logins.with_label_values(&[method, outcome]).inc();
+    }
+
+    pub fn query(&self, operation: &str, took: Duration) {
+        self.query_seconds
+            .with_label_values(&[operation])
+            .observe(took.as_secs_f64());
+    }
+
+    /// The Prometheus text exposition, with the broadcast gauges read from `broadcasts` now.
+    pub fn render(&self, broadcasts: &HashMap<Uuid, Broadcast>) -> String {
+        self.live_broadcasts.set(broadcasts.len() as i64);
+
+        // Ended broadcasts shouldn't linger as series
+        self.subscribers.reset();
+        for (id, broadcast) in broadcasts {
+            self.subscribers
+                .with_label_values(&[&id.to_string()])
+                .set(broadcast.subs.len() as i64);
+        }
+
+        let mut buffer = Vec::new();
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
@@ -1,11 +1,49 @@
+mod admin;
+mod api_key;
 mod application_state;
 mod auth;
+mod auth_failures;
 mod broadcast;
+mod bus;
+mod config;
 mod db_controller;
+mod error;
+mod export;
+mod health;
 mod jwt;
+mod metrics;
+mod oidc;
+mod profile;
+mod repository;
+mod revocation;
+mod session;
+mod throttle;
+pub mod tls;
+mod totp;
 
+pub use admin::{AuthFailuresResponse, LiveBroadcast, LiveBroadcastsResponse};
+pub use api_key::{ApiKeyPrincipal, ApiKeyRequest, ApiKeyResponse, ApiKeyScope, API_KEY_PREFIX};
 pub use application_state::ApplicationState;
-pub use auth::{Auth, AuthResponse, UserRegistrationRequest, UserAccessRequest};
+pub use auth::{Auth, AuthResponse, Role, Team, User, UserAccessRequest, UserRegistrationRequest};
+pub use auth_failures::AuthFailure;
 pub use broadcast::Broadcast;
-pub use jwt::JwtManager;
-pub use db_controller::DbController;
+pub use bus::{BroadcastBus, Channels};
+pub use config::{Config, LogFormat, TlsConfig, TokenConfig};
+pub use db_controller::{DbController, LoginOutcome};
+pub use error::{AppError, ErrorResponse};
+pub use export::Export;
+pub use health::{HealthResponse, ReadinessCheck, ReadinessResponse};
+pub use jwt::{AccessTokenClaims, JwtManager};
+pub use metrics::Metrics;
+pub use oidc::{OidcCallbackRequest, OidcClient, OidcConfig, OidcLoginRequest};
+pub use profile::{
+    AccountDeletionRequest, EmailChangeRequest, MirrorMode, PasswordChangeRequest, Profile,
+    ProfileResponse, ProfileUpdateRequest,
+};
+pub use revocation::RevocationList;
+pub use session::{SessionDevice, SessionsResponse};
+pub use throttle::LoginThrottle;
+pub use totp::{
+    RecoveryCodesResponse, TotpChallengeResponse, TotpCodeRequest, TotpEnrollmentResponse,
+    TotpLoginRequest,
+};
@@ -0,0 +1,320 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::types::error::AppError;
+
+/// How long the provider's metadata and signing keys are used before being fetched again.
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+/// How long a request to the identity provider may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signatures accepted on ID tokens. Whatever the token's header claims, `none` and HMAC
+/// among them, nothing else is.
+const ID_TOKEN_ALGORITHMS: [Algorithm; 2] = [Algorithm::RS256, Algorithm::ES256];
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// Something fetched from the identity provider, and when.
+#[derive(Debug)]
+struct Fetched<T> {
+    value: Arc<T>,
+    at: Instant,
+}
+
+impl<T> Fetched<T> {
+    fn fresh(slot: &Option<Self>) -> Option<Arc<T>> {
+        slot.as_ref()
+            .filter(|fetched| fetched.at.elapsed() < METADATA_TTL)
+            .map(|fetched| fetched.value.clone())
+    }
+
+    fn store(slot: &mut Option<Self>, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        *slot = Some(Fetched {
+            value: value.clone(),
+            at: Instant::now(),
+        });
+        value
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    nonce: Option<String>,
+}
+
+/// What the callback needs from the start of a login. It travels in a signed cookie, so the
+/// callback only completes in the browser that started it, on whichever instance it lands.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingLogin {
+    pub state: String,
+    nonce: String,
+    code_verifier: String,
+    device: Option<String>,
+}
+
+/// A user the identity provider vouched for.
+#[derive(Debug)]
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OidcLoginRequest {
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Runs the OpenID Connect authorization code flow with PKCE against a single issuer.
+#[derive(Debug)]
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: Mutex<Option<Fetched<ProviderMetadata>>>,
+    keys: Mutex<Option<Fetched<JwkSet>>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("HTTP client");
+
+        Self {
+            config,
+            http,
+            metadata: Mutex::new(None),
+            keys: Mutex::new(None),
+        }
+    }
+
+    /// The provider's metadata, fetched at most once an hour. Holding the lock while fetching
+    /// keeps a burst of logins down to one request.
+    async fn discover(&self) -> Result<Arc<ProviderMetadata>, AppError> {
+        let mut cached = self.metadata.lock().await;
+        if let Some(metadata) = Fetched::fresh(&cached) {
+            return Ok(metadata);
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+        let metadata: ProviderMetadata = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| AppError::Upstream("Identity provider is unavailable".to_string()))?
+            .json()
+            .await
+            .map_err(|_| {
+                AppError::Upstream("Identity provider sent invalid metadata".to_string())
+            })?;
+
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+            return Err(AppError::Upstream(
+                "Identity provider sent invalid metadata".to_string(),
+            ));
+        }
+
+        Ok(Fetched::store(&mut cached, metadata))
+    }
+
+    /// The provider's signing keys, cached like its metadata. `refresh` fetches them anyway, for
+    /// when a token is signed with a key that's newer than the cache.
+    async fn keys(&self, jwks_uri: &str, refresh: bool) -> Result<Arc<JwkSet>, AppError> {
+        let mut cached = self.keys.lock().await;
+        if let Some(keys) = Fetched::fresh(&cached).filter(|_| !refresh) {
+            return Ok(keys);
+        }
+
+        let keys: JwkSet = self
+            .http
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| AppError::Upstream("Identity provider is unavailable".to_string()))?
+            .json()
+            .await
+            .map_err(|_| AppError::Upstream("Identity provider sent invalid keys".to_string()))?;
+
+        Ok(Fetched::store(&mut cached, keys))
+    }
+
+    /// Builds the URL to send the browser to, along with the state, nonce and PKCE verifier the
+    /// callback will need.
+    pub async fn authorization_url(
+        &self,
+        device: Option<String>,
+    ) -> Result<(String, PendingLogin), AppError> {
+        let metadata = self.discover().await?;
+
+        let state = random_string(32);
+        let nonce = random_string(32);
+        let code_verifier = random_string(64);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint).map_err(|_| {
+            AppError::Upstream("Identity provider sent invalid metadata".to_string())
+        })?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", "openid email profile")
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        let login = PendingLogin {
+            state,
+            nonce,
+            code_verifier,
+            device,
+        };
+        Ok((url.to_string(), login))
+    }
+
+    /// Exchanges the authorization code and validates the ID token that comes back. The caller
+    /// has already checked the callback's state against the login's.
+    pub async fn exchange(
+        &self,
+        code: &str,
+        login: PendingLogin,
+    ) -> Result<OidcIdentity, AppError> {
+        let metadata = self.discover().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login.code_verifier.as_str()),
+        ];
+        if let Some(client_secret) = self.config.client_secret.as_deref() {
+            form.push(("client_secret", client_secret));
+        }
+
+        let tokens: TokenResponse = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| AppError::Upstream("Identity provider rejected the login".to_string()))?
+            .json()
+            .await
+            .map_err(|_| {
+                AppError::Upstream("Identity provider sent an invalid response".to_string())
+            })?;
+
+        let claims = self.validate_id_token(&metadata, &tokens.id_token).await?;
+
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            return Err(AppError::InvalidToken);
+        }
+
+        Ok(OidcIdentity {
+            issuer: self.config.issuer.clone(),
+            subject: claims.sub,
+            email: claims.email,
+            email_verified: claims.email_verified.unwrap_or(false),
+            device: login.device,
+        })
+    }
+
+    async fn validate_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let Ok(header) = decode_header(id_token) else {
+            return Err(AppError::InvalidToken);
+        };
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(AppError::InvalidToken);
+        }
+
+        let find = |jwks: &JwkSet| match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+        let mut jwk = find(&*self.keys(&metadata.jwks_uri, false).await?);
+        // Providers rotate keys, so an unknown one may just be newer than the cache
+        if jwk.is_none() {
+            jwk = find(&*self.keys(&metadata.jwks_uri, true).await?);
+        }
+        let Some(jwk) = jwk else {
+            return Err(AppError::InvalidToken);
+        };
+        // A key published for one algorithm can't be used with another
+        if let Some(key_algorithm) = jwk.common.key_algorithm {
+            if key_algorithm.to_string() != format!("{:?}", header.alg) {
+                return Err(AppError::InvalidToken);
+            }
+        }
+        let Ok(key) = DecodingKey::from_jwk(&jwk) else {
+            return Err(AppError::InvalidToken);
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&[&metadata.issuer]);
+
+        decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map(|token| token.claims)
+            .map_err(|_| AppError::InvalidToken)
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::error::AppError;
+
+/// Scroll speeds match the `scroll:speed_1` to `scroll:speed_5` broadcast commands.
+pub const SCROLL_SPEEDS: std::ops::RangeInclusive<i16> = 1..=5;
+pub const FONT_SIZES: std::ops::RangeInclusive<i16> = 12..=200;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorMode {
+    #[default]
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+impl MirrorMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MirrorMode::None => "none",
+            MirrorMode::Horizontal => "horizontal",
+            MirrorMode::Vertical => "vertical",
+            MirrorMode::Both => "both",
+        }
+    }
+}
+
+impl From<&str> for MirrorMode {
+    fn from(mode: &str) -> Self {
+        match mode {
+            "horizontal" => MirrorMode::Horizontal,
+            "vertical" => MirrorMode::Vertical,
+            "both" => MirrorMode::Both,
+            _ => MirrorMode::None,
+        }
+    }
+}
+
+/// A user's account details and the defaults their viewers start with.
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub email: String,
+    pub display_name: Option<String>,
+    pub scroll_speed: i16,
+    pub font_size: i16,
+    pub mirror_mode: MirrorMode,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProfileUpdateRequest {
+    pub display_name: Option<String>,
+    pub scroll_speed: Option<i16>,
+    pub font_size: Option<i16>,
+    pub mirror_mode: Option<MirrorMode>,
+}
+
+impl ProfileUpdateRequest {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if let Some(display_name) = &self.display_name {
+            if display_name.trim().is_empty() || display_name.chars().count() > 64 {
+                return Err(AppError::Validation(
+                    "Display name must be between 1 and 64 characters".to_string(),
+                ));
+            }
+        }
+
+        if self
+            .scroll_speed
+            .is_some_and(|speed| !SCROLL_SPEEDS.contains(&speed))
+        {
+            return Err(AppError::Validation(
+                "Scroll speed must be between 1 and 5".to_string(),
+            ));
+        }
+
+        if self
+            .font_size
+            .is_some_and(|size| !FONT_SIZES.contains(&size))
+        {
+            return Err(AppError::Validation(
+                "Font size must be between 12 and 200".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EmailChangeRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PasswordChangeRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountDeletionRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileResponse {
+    success: bool,
+    profile: Profile,
+}
+
+impl ProfileResponse {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            success: true,
+            profile,
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{
+    nothing_to_baseline, ControllerLease, Credentials, NewSession, Repository, StoredApiKey,
+    StoredBroadcast,
+};
+use crate::types::{
+    api_key::{ApiKey, ApiKeyScope},
+    auth::{Auth, Role, Team, User},
+    error::AppError,
+    profile::{Profile, ProfileUpdateRequest},
+    session::{Session, SessionDevice},
+};
+
+#[derive(Debug)]
+struct StoredAuth {
+    user: User,
+    credentials: Credentials,
+    profile: Profile,
+    totp_last_step: Option<i64>,
+}
+
+#[derive(Debug)]
+struct StoredSession {
+    auth: String,
+    refresh_token: String,
+    device: SessionDevice,
+    created_at: DateTime<Utc>,
+    last_used: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct StoredRecoveryCode {
+    auth: String,
+    hash: String,
+    used: bool,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    teams: HashMap<Uuid, Team>,
+    auths: HashMap<String, StoredAuth>,
+    oidc_identities: HashMap<(String, String), String>,
+    sessions: HashMap<String, StoredSession>,
+    revoked_tokens: HashMap<String, DateTime<Utc>>,
+    api_keys: HashMap<Uuid, (ApiKey, StoredApiKey)>,
+    recovery_codes: HashMap<String, StoredRecoveryCode>,
+    broadcasts: HashMap<Uuid, (StoredBroadcast, DateTime<Utc>)>,
+}
+
+impl Store {
+    /// Stands in for the unique index on `auths.email`.
+    fn email_taken(&self, email: &str) -> bool {
+        self.auths
+            .values()
+            .any(|auth| auth.user.email.eq_ignore_ascii_case(email))
+    }
+}
+
+/// Keeps everything in process memory, for development and tests. Nothing survives a restart.
+#[derive(Debug, Default)]
+pub struct MemoryRepository {
+    store: Mutex<Store>,
+}
+
+impl MemoryRepository {
+    fn store(&self) -> MutexGuard<'_, Store> {
+        // A panic elsewhere can't leave a half-written entry behind, so keep serving
+        self.store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl Repository for MemoryRepository {
+    async fn migrate(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> Result<usize, AppError> {
+        Ok(0)
+    }
+
+    async fn baseline(&self) -> Result<(), AppError> {
+        Err(nothing_to_baseline())
+    }
+
+    async fn user(&self, id: &str) -> Result<Option<User>, AppError> {
+        Ok(self.store().auths.get(id).map(|auth| auth.user.clone()))
+    }
+
+    async fn credentials(&self, id: &str) -> Result<Option<Credentials>, AppError> {
+        Ok(self
+            .store()
+            .auths
+            .get(id)
+            .map(|auth| auth.credentials.clone()))
+    }
+
+    async fn credentials_by_email(&self, email: &str) -> Result<Option<Credentials>, AppError> {
+        Ok(self
+            .store()
+            .auths
+            .values()
+            .find(|auth| auth.user.email.eq_ignore_ascii_case(email))
+            .map(|auth| auth.credentials.clone()))
+    }
+
+    async fn insert_auth(&self, auth: &Auth) -> Result<(), AppError> {
+        let mut store = self.store();
+        if store.email_taken(&auth.email) {
+            return Err(AppError::Conflict("That already exists".to_string()));
+        }
+        let team = match auth.team {
+            Some(team) => Some(store.teams.get(&team).cloned().ok_or_else(|| {
+                AppError::Validation("That refers to something that doesn't exist".to_string())
+            })?),
+            None => None,
+        };
+
+        store.auths.insert(
+            auth.id.to_string(),
+            StoredAuth {
+                user: User {
+                    id: auth.id,
+                    email: auth.email.clone(),
+                    role: Role::default(),
+                    team,
+                    disabled: false,
+                },
+                credentials: Credentials {
+                    id: auth.id.to_string(),
+                    hash: auth.hash.clone(),
+                    totp_secret: None,
+                    totp_enabled: false,
+                },
+                profile: Profile {
+                    email: auth.email.clone(),
+                    display_name: None,
+                    scroll_speed: 3,
+                    font_size: 48,
+                    mirror_mode: Default::default(),
+                },
+                totp_last_step: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn update_email(&self, id: &str, email: &str) -> Result<(), AppError> {
+        let mut store = self.store();
+        if store.email_taken(email) {
+            return Err(AppError::Conflict("That already exists".to_string()));
+        }
+        if let Some(auth) = store.auths.get_mut(id) {
+            auth.user.email = email.to_string();
+            auth.profile.email = email.to_string();
+        }
+        Ok(())
+    }
+
+    async fn update_hash(&self, id: &str, hash: &str) -> Result<(), AppError> {
+        if let Some(auth) = self.store().auths.get_mut(id) {
+            auth.credentials.hash = hash.to_string();
+        }
+        Ok(())
+    }
+
+    async fn users(&self) -> Result<Vec<User>, AppError> {
+        let mut users: Vec<User> = self
+            .store()
+            .auths
+            .values()
+            .map(|auth| auth.user.clone())
+            .collect();
+        users.sort_by(|a, b| a.email.cmp(&b.email));
+        Ok(users)
+    }
+
+    async fn set_disabled(&self, id: &str, disabled: bool) -> Result<(), AppError> {
+        if let Some(auth) = self.store().auths.get_mut(id) {
+            auth.user.disabled = disabled;
+        }
+        Ok(())
+    }
+
+    async fn update_role(&self, id: &str, role: Role) -> Result<(), AppError> {
+        if let Some(auth) = self.store().auths.get_mut(id) {
+            auth.user.role = role;
+        }
+        Ok(())
+    }
+
+    async fn insert_team(&self, team: &Team) -> Result<(), AppError> {
+        self.store().teams.insert(team.id, team.clone());
+        Ok(())
+    }
+
+    async fn teams(&self) -> Result<Vec<Team>, AppError> {
+        let mut teams: Vec<Team> = self.store().teams.values().cloned().collect();
+        teams.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(teams)
+    }
+
+    async fn delete_auth(&self, id: &str) -> Result<(), AppError> {
+        let mut store = self.store();
+
+        store.auths.remove(id);
+        store.sessions.retain(|_, session| session.auth != id);
+        store.recovery_codes.retain(|_, code| code.auth != id);
+        store.oidc_identities.retain(|_, auth| auth != id);
+
+        Ok(())
+    }
+
+    async fn profile(&self, id: &str) -> Result<Option<Profile>, AppError> {
+        Ok(self.store().auths.get(id).map(|auth| auth.profile.clone()))
+    }
+
+    async fn update_profile(
+        &self,
+        id: &str,
+        request: &ProfileUpdateRequest,
+    ) -> Result<(), AppError> {
+        let mut store = self.store();
+        let Some(auth) = store.auths.get_mut(id) else {
+            return Ok(());
+        };
+
+        if let Some(display_name) = &request.display_name {
+            auth.profile.display_name = Some(display_name.trim().to_string());
+        }
+        if let Some(scroll_speed) = request.scroll_speed {
+            auth.profile.scroll_speed = scroll_speed;
+        }
+        if let Some(font_size) = request.font_size {
+            auth.profile.font_size = font_size;
+        }
+        if let Some(mirror_mode) = request.mirror_mode {
+            auth.profile.mirror_mode = mirror_mode;
+        }
+
+        Ok(())
+    }
+
+    async fn oidc_identity(&self, issuer: &str, subject: &str) -> Result<Option<String>, AppError> {
+        Ok(self
+            .store()
+            .oidc_identities
+            .get(&(issuer.to_string(), subject.to_string()))
+            .cloned())
+    }
+
+    async fn link_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+        auth: &str,
+    ) -> Result<(), AppError> {
+        self.store()
+            .oidc_identities
+            .insert((issuer.to_string(), subject.to_string()), auth.to_string());
+        Ok(())
+    }
+
+    async fn insert_session(&self, session: NewSession<'_>) -> Result<(), AppError> {
+        let now = Utc::now();
+        self.store().sessions.insert(
+            session.id.to_string(),
+            StoredSession {
+                auth: session.auth.to_string(),
+                refresh_token: session.refresh_token.to_string(),
+                device: session.device,
+                created_at: now,
+                last_used: now,
+            },
+        );
+        Ok(())
+    }
+
+    async fn touch_session(
+        &self,
+        id: &str,
+        auth: &str,
+        refresh_token: &str,
+    ) -> Result<bool, AppError> {
+        let mut store = self.store();
+        match store.sessions.get_mut(id) {
+            Some(session) if session.auth == auth && session.refresh_token == refresh_token => {
+                session.last_used = Utc::now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn sessions(&self, auth: &str) -> Result<Vec<Session>, AppError> {
+        let mut sessions: Vec<Session> = self
+            .store()
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.auth == auth)
+            .map(|(id, session)| Session {
+                id: id.clone(),
+                device_label: session.device.label.clone(),
+                user_agent: session.device.user_agent.clone(),
+                ip_address: session.device.ip_address.clone(),
+                created_at: session.created_at,
+                last_used: session.last_used,
+                current: false,
+            })
+            .collect();
+
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_used));
+        Ok(sessions)
+    }
+
+    async fn session_exists(&self, id: &str, auth: &str) -> Result<bool, AppError> {
+        Ok(self
+            .store()
+            .sessions
+            .get(id)
+            .is_some_and(|session| session.auth == auth))
+    }
+
+    async fn delete_session(&self, id: &str, auth: &str) -> Result<bool, AppError> {
+        let mut store = self.store();
+        if store
+            .sessions
+            .get(id)
+            .is_some_and(|session| session.auth == auth)
+        {
+            store.sessions.remove(id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn delete_sessions(&self, auth: &str, keep: Option<&str>) -> Result<(), AppError> {
+        self.store()
+            .sessions
+            .retain(|id, session| session.auth != auth || Some(id.as_str()) == keep);
+        Ok(())
+    }
+
+    async fn insert_revoked_token(
+        &self,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.store()
+            .revoked_tokens
+            .insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    async fn revoked_tokens(&self) -> Result<Vec<(String, DateTime<Utc>)>, AppError> {
+        let mut store = self.store();
+        let now = Utc::now();
+        store
+            .revoked_tokens
+            .retain(|_, expires_at| *expires_at > now);
+
+        Ok(store
+            .revoked_tokens
+            .iter()
+            .map(|(jti, expires_at)| (jti.clone(), *expires_at))
+            .collect())
+    }
+
+    async fn insert_api_key(
+        &self,
+        api_key: &ApiKey,
+        team: &Uuid,
+        hash: &str,
+        _created_by: &Uuid,
+    ) -> Result<(), AppError> {
+        let stored = StoredApiKey {
+            team: *team,
+            hash: hash.to_string(),
+            scopes: ApiKeyScope::join(&api_key.scopes),
+        };
+
+        self.store()
+            .api_keys
+            .insert(api_key.id, (api_key.clone(), stored));
+        Ok(())
+    }
+
+    async fn api_keys(&self, team: &Uuid) -> Result<Vec<ApiKey>, AppError> {
+        let mut api_keys: Vec<ApiKey> = self
+            .store()
+            .api_keys
+            .values()
+            .filter(|(_, stored)| stored.team == *team)
+            .map(|(api_key, _)| api_key.clone())
+            .collect();
+
+        api_keys.sort_by_key(|api_key| std::cmp::Reverse(api_key.created_at));
+        Ok(api_keys)
+    }
+
+    async fn stored_api_key(&self, id: &Uuid) -> Result<Option<StoredApiKey>, AppError> {
+        Ok(self
+            .store()
+            .api_keys
+            .get(id)
+            .map(|(_, stored)| stored.clone()))
+    }
+
+    async fn touch_api_key(&self, id: &Uuid) -> Result<(), AppError> {
+        if let Some((api_key, _)) = self.store().api_keys.get_mut(id) {
+            api_key.last_used = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    async fn delete_api_key(&self, team: &Uuid, id: &str) -> Result<bool, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(false);
+        };
+
+        let mut store = self.store();
+        if store
+            .api_keys
+            .get(&id)
+            .is_some_and(|(_, stored)| stored.team == *team)
+        {
+            store.api_keys.remove(&id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn set_totp_secret(&self, id: &str, secret: &str) -> Result<(), AppError> {
+        if let Some(auth) = self.store().auths.get_mut(id) {
+            auth.credentials.totp_secret = Some(secret.to_string());
+        }
+        Ok(())
+    }
+
+    async fn enable_totp(&self, id: &str, recovery_code_hashes: &[String]) -> Result<(), AppError> {
+        let mut store = self.store();
+        let Some(auth) = store.auths.get_mut(id) else {
+            return Ok(());
+        };
+        auth.credentials.totp_enabled = true;
+
+        store.recovery_codes.retain(|_, code| code.auth != id);
+        for hash in recovery_code_hashes {
+            store.recovery_codes.insert(
+                Uuid::new_v4().to_string(),
+                StoredRecoveryCode {
+                    auth: id.to_string(),
+                    hash: hash.clone(),
+                    used: false,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn unused_recovery_codes(&self, auth: &str) -> Result<Vec<(String, String)>, AppError> {
+        Ok(self
+            .store()
+            .recovery_codes
+            .iter()
+            .filter(|(_, code)| code.auth == auth && !code.used)
+            .map(|(id, code)| (id.clone(), code.hash.clone()))
+            .collect())
+    }
+
+    async fn use_totp_step(&self, id: &str, step: i64) -> Result<bool, AppError> {
+        match self.store().auths.get_mut(id) {
+            Some(auth) if auth.totp_last_step.is_none_or(|last| last < step) => {
+                auth.totp_last_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn use_recovery_code(&self, id: &str) -> Result<bool, AppError> {
+        match self.store().recovery_codes.get_mut(id) {
+            Some(code) if !code.used => {
+                code.used = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn save_broadcast(&self, broadcast: &StoredBroadcast) -> Result<(), AppError> {
+        let mut store = self.store();
+        let lease = store
+            .broadcasts
+            .get(&broadcast.id)
+            .and_then(|(saved, _)| saved.lease.clone());
+        let broadcast = StoredBroadcast {
+            lease,
+            ..broadcast.clone()
+        };
+        store
+            .broadcasts
+            .insert(broadcast.id, (broadcast, Utc::now()));
+        Ok(())
+    }
+
+    async fn delete_broadcast(&self, id: &Uuid) -> Result<(), AppError> {
+        self.store().broadcasts.remove(id);
+        Ok(())
+    }
+
+    async fn broadcast(&self, id: &Uuid) -> Result<Option<StoredBroadcast>, AppError> {
+        Ok(self
+            .store()
+            .broadcasts
+            .get(id)
+            .map(|(broadcast, _)| broadcast.clone()))
+    }
+
+    async fn broadcasts(&self, cutoff: DateTime<Utc>) -> Result<Vec<StoredBroadcast>, AppError> {
+        let mut store = self.store();
+        store
+            .broadcasts
+            .retain(|_, (_, updated_at)| *updated_at >= cutoff);
+
+        Ok(store
+            .broadcasts
+            .values()
+            .map(|(broadcast, _)| broadcast.clone())
+            .collect())
+    }
+
+    async fn claim_broadcast(
+        &self,
+        id: &Uuid,
+        instance: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut store = self.store();
+        let Some((broadcast, _)) = store.broadcasts.get_mut(id) else {
+            return Ok(false);
+        };
+        let taken = broadcast
+            .lease
+            .as_ref()
+            .is_some_and(|lease| lease.instance != instance && lease.is_live());
+        if taken {
+            return Ok(false);
+        }
+
+        broadcast.lease = Some(ControllerLease {
+            instance: instance.to_string(),
+            expires_at,
+        });
+        Ok(true)
+    }
+
+    async fn release_broadcast(&self, id: &Uuid, instance: &str) -> Result<(), AppError> {
+        if let Some((broadcast, _)) = self.store().broadcasts.get_mut(id) {
+            if broadcast
+                .lease
+                .as_ref()
+                .is_some_and(|lease| lease.instance == instance)
+            {
+                broadcast.lease = None;
+            }
+        }
+        Ok(())
+    }
+}
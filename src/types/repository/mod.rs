@@ -0,0 +1,212 @@
+mod memory;
+mod postgres;
+mod sql;
+
+use std::{error::Error, fmt::Debug};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::migrate::Migrator;
+use uuid::Uuid;
+
+use crate::types::{
+    api_key::ApiKey,
+    auth::{Auth, Role, Team, User},
+    broadcast::BroadcastState,
+    error::AppError,
+    profile::{Profile, ProfileUpdateRequest},
+    session::{Session, SessionDevice},
+};
+
+pub use memory::MemoryRepository;
+pub use postgres::PostgresRepository;
+pub use sql::{MySqlRepository, SqliteRepository};
+
+/// What's stored to check who an account belongs to.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub id: String,
+    pub hash: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+}
+
+#[derive(Debug)]
+pub struct NewSession<'a> {
+    pub id: &'a str,
+    pub auth: &'a str,
+    pub refresh_token: &'a str,
+    pub device: SessionDevice,
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredApiKey {
+    pub team: Uuid,
+    pub hash: String,
+    pub scopes: String,
+}
+
+/// A live broadcast as saved, so it can be restored after a restart or crash.
+#[derive(Debug, Clone)]
+pub struct StoredBroadcast {
+    pub id: Uuid,
+    pub owner: Option<Uuid>,
+    pub team: Option<Uuid>,
+    pub state: BroadcastState,
+    /// Who has the controller, as last read. Saving leaves it alone: it's only changed by
+    /// `claim_broadcast` and `release_broadcast`.
+    pub lease: Option<ControllerLease>,
+}
+
+/// An instance's claim on a broadcast's controller, renewed while the controller stays connected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerLease {
+    pub instance: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ControllerLease {
+    /// Whether the lease still keeps other instances from taking the controller.
+    pub fn is_live(&self) -> bool {
+        self.expires_at > Utc::now()
+    }
+}
+
+/// Storage for accounts, sessions and everything hanging off them. `DbController` keeps the
+/// rules (hashing, token minting, two-factor) so each backend only has to store and fetch.
+#[async_trait]
+pub trait Repository: Debug + Send + Sync {
+    /// Brings the schema up to date. Backends without one have nothing to do.
+    async fn migrate(&self) -> Result<(), AppError>;
+    /// A round trip to the database, proving it's reachable.
+    async fn ping(&self) -> Result<(), AppError>;
+    /// Embedded migrations this database hasn't applied yet.
+    async fn pending_migrations(&self) -> Result<usize, AppError>;
+    /// Records the initial migration as applied to a database built by hand from the old
+    /// `schemas.sql`, after checking it has every table and column that migration creates.
+    async fn baseline(&self) -> Result<(), AppError>;
+
+    async fn user(&self, id: &str) -> Result<Option<User>, AppError>;
+    async fn credentials(&self, id: &str) -> Result<Option<Credentials>, AppError>;
+    async fn credentials_by_email(&self, email: &str) -> Result<Option<Credentials>, AppError>;
+    async fn insert_auth(&self, auth: &Auth) -> Result<(), AppError>;
+    async fn update_email(&self, id: &str, email: &str) -> Result<(), AppError>;
+    async fn update_hash(&self, id: &str, hash: &str) -> Result<(), AppError>;
+    /// Removes the account along with its sessions, recovery codes and linked identities.
+    async fn delete_auth(&self, id: &str) -> Result<(), AppError>;
+    /// Every account, ordered by email.
+    async fn users(&self) -> Result<Vec<User>, AppError>;
+    async fn set_disabled(&self, id: &str, disabled: bool) -> Result<(), AppError>;
+    async fn update_role(&self, id: &str, role: Role) -> Result<(), AppError>;
+    async fn insert_team(&self, team: &Team) -> Result<(), AppError>;
+    /// Every team, ordered by name.
+    async fn teams(&self) -> Result<Vec<Team>, AppError>;
+    async fn profile(&self, id: &str) -> Result<Option<Profile>, AppError>;
+    /// Fields left out of the request keep their current value.
+    async fn update_profile(
+        &self,
+        id: &str,
+        request: &ProfileUpdateRequest,
+    ) -> Result<(), AppError>;
+
+    async fn oidc_identity(&self, issuer: &str, subject: &str) -> Result<Option<String>, AppError>;
+    async fn link_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+        auth: &str,
+    ) -> Result<(), AppError>;
+
+    async fn insert_session(&self, session: NewSession<'_>) -> Result<(), AppError>;
+    /// Marks the session used if the refresh token still belongs to it.
+    async fn touch_session(
+        &self,
+        id: &str,
+        auth: &str,
+        refresh_token: &str,
+    ) -> Result<bool, AppError>;
+    async fn sessions(&self, auth: &str) -> Result<Vec<Session>, AppError>;
+    async fn session_exists(&self, id: &str, auth: &str) -> Result<bool, AppError>;
+    async fn delete_session(&self, id: &str, auth: &str) -> Result<bool, AppError>;
+    /// Deletes every session the account has, except `keep` if given.
+    async fn delete_sessions(&self, auth: &str, keep: Option<&str>) -> Result<(), AppError>;
+
+    async fn insert_revoked_token(
+        &self,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+    /// Prunes expired revocations and returns the rest.
+    async fn revoked_tokens(&self) -> Result<Vec<(String, DateTime<Utc>)>, AppError>;
+
+    async fn insert_api_key(
+        &self,
+        api_key: &ApiKey,
+        team: &Uuid,
+        hash: &str,
+        created_by: &Uuid,
+    ) -> Result<(), AppError>;
+    async fn api_keys(&self, team: &Uuid) -> Result<Vec<ApiKey>, AppError>;
+    async fn stored_api_key(&self, id: &Uuid) -> Result<Option<StoredApiKey>, AppError>;
+    async fn touch_api_key(&self, id: &Uuid) -> Result<(), AppError>;
+    async fn delete_api_key(&self, team: &Uuid, id: &str) -> Result<bool, AppError>;
+
+    async fn set_totp_secret(&self, id: &str, secret: &str) -> Result<(), AppError>;
+    /// Turns two-factor on and replaces any previous recovery codes, all or nothing.
+    async fn enable_totp(&self, id: &str, recovery_code_hashes: &[String]) -> Result<(), AppError>;
+    /// Records the time step of a TOTP code just accepted, returning false if that step or a
+    /// later one was already used.
+    async fn use_totp_step(&self, id: &str, step: i64) -> Result<bool, AppError>;
+    /// Returns the id and hash of each recovery code that hasn't been used.
+    async fn unused_recovery_codes(&self, auth: &str) -> Result<Vec<(String, String)>, AppError>;
+    /// Burns a recovery code, returning false if it was already used.
+    async fn use_recovery_code(&self, id: &str) -> Result<bool, AppError>;
+
+    /// Inserts the broadcast or updates its saved state.
+    async fn save_broadcast(&self, broadcast: &StoredBroadcast) -> Result<(), AppError>;
+    async fn delete_broadcast(&self, id: &Uuid) -> Result<(), AppError>;
+    async fn broadcast(&self, id: &Uuid) -> Result<Option<StoredBroadcast>, AppError>;
+    /// Deletes broadcasts not saved since `cutoff` and returns the rest.
+    async fn broadcasts(&self, cutoff: DateTime<Utc>) -> Result<Vec<StoredBroadcast>, AppError>;
+    /// Takes or renews the controller lease for `instance`, returning false if the broadcast is
+    /// gone or another instance holds a lease that hasn't expired.
+    async fn claim_broadcast(
+        &self,
+        id: &Uuid,
+        instance: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+    /// Gives up the controller lease, if `instance` still holds it.
+    async fn release_broadcast(&self, id: &Uuid, instance: &str) -> Result<(), AppError>;
+}
+
+/// `schemas.sql` was only ever applied to MySQL, so other backends have nothing to adopt.
+fn nothing_to_baseline() -> AppError {
+    AppError::Validation(
+        "Only MySQL databases were built from schemas.sql; run migrate instead".to_string(),
+    )
+}
+
+/// Migrations in `migrator` missing from the versions sqlx recorded as applied.
+fn pending(migrator: &Migrator, applied: &[i64]) -> usize {
+    migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .count()
+}
+
+/// Picks a backend from the URL scheme: `mysql://`, `postgres://`, `sqlite:` or `memory:`.
+pub async fn connect(
+    database_url: &str,
+) -> Result<Box<dyn Repository>, Box<dyn Error + Send + Sync>> {
+    let scheme = database_url.split(':').next().unwrap_or_default();
+
+    match scheme {
+        "mysql" | "mariadb" => Ok(Box::new(MySqlRepository::connect(database_url).await?)),
+        "postgres" | "postgresql" => Ok(Box::new(PostgresRepository::connect(database_url).await?)),
+        "sqlite" => Ok(Box::new(SqliteRepository::connect(database_url).await?)),
+        "memory" => Ok(Box::new(MemoryRepository::default())),
+        _ => Err(format!("Unsupported database URL scheme `{scheme}`").into()),
+    }
+}
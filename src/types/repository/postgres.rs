@@ -0,0 +1,714 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, PgPool, Row};
+use uuid::Uuid;
+
+use super::{
+    nothing_to_baseline, pending, ControllerLease, Credentials, NewSession, Repository,
+    StoredApiKey, StoredBroadcast,
+};
+use crate::types::{
+    api_key::{ApiKey, ApiKeyScope},
+    auth::{Auth, Role, Team, User},
+    broadcast::BroadcastState,
+    error::AppError,
+    profile::{MirrorMode, Profile, ProfileUpdateRequest},
+    session::Session,
+};
+
+static MIGRATIONS: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Stores ids as native `UUID`s and times as `TIMESTAMPTZ`. Ids that don't parse can't match
+/// a row, so they're treated like missing ones rather than sent to the database.
+#[derive(Debug)]
+pub struct PostgresRepository {
+    pool: PgPool,
+}
+
+impl PostgresRepository {
+    pub async fn connect(database_url: &str) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            pool: PgPoolOptions::new().connect(database_url).await?,
+        })
+    }
+}
+
+/// Reads a row of `auths` joined with its team as `team_id` and `team_name`.
+fn user(row: &sqlx::postgres::PgRow) -> User {
+    User {
+        id: row.get("id"),
+        email: row.get("email"),
+        role: Role::from(row.get::<&str, _>("role")),
+        disabled: row.get("disabled"),
+        team: row.get::<Option<Uuid>, _>("team_id").map(|team_id| Team {
+            id: team_id,
+            name: row.get("team_name"),
+        }),
+    }
+}
+
+fn credentials(row: &sqlx::postgres::PgRow) -> Credentials {
+    Credentials {
+        id: row.get::<Uuid, _>("id").to_string(),
+        hash: row.get("hash"),
+        totp_secret: row.get("totp_secret"),
+        totp_enabled: row.get("totp_enabled"),
+    }
+}
+
+fn stored_broadcast(row: &sqlx::postgres::PgRow) -> StoredBroadcast {
+    StoredBroadcast {
+        id: row.get("id"),
+        owner: row.get("owner"),
+        team: row.get("team"),
+        state: BroadcastState {
+            script: row.get("script"),
+            speed: row.get("speed"),
+            scrolling: row.get("scrolling"),
+            position: row.get("position"),
+            timer: row.get("timer"),
+        },
+        lease: row
+            .get::<Option<String>, _>("controller_instance")
+            .zip(row.get("lease_expires_at"))
+            .map(|(instance, expires_at)| ControllerLease {
+                instance,
+                expires_at,
+            }),
+    }
+}
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    async fn migrate(&self) -> Result<(), AppError> {
+        MIGRATIONS.run(&self.pool).await.map_err(AppError::internal)
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> Result<usize, AppError> {
+        // A database that was never migrated has no table to read; `ping` covers it being down
+        let applied: Vec<i64> =
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+                .fetch_all(&self.pool)
+                .await
+                .unwrap_or_default();
+
+        Ok(pending(&MIGRATIONS, &applied))
+    }
+
+    async fn baseline(&self) -> Result<(), AppError> {
+        Err(nothing_to_baseline())
+    }
+
+    async fn user(&self, id: &str) -> Result<Option<User>, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(None);
+        };
+
+        let row = sqlx::query(
+            "SELECT auths.id, auths.email, auths.role, auths.disabled, teams.id AS team_id, teams.name AS team_name FROM auths LEFT JOIN teams ON auths.team = teams.id WHERE auths.id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(user))
+    }
+
+    async fn credentials(&self, id: &str) -> Result<Option<Credentials>, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(None);
+        };
+
+        let row =
+            sqlx::query("SELECT id, hash, totp_secret, totp_enabled FROM auths WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.as_ref().map(credentials))
+    }
+
+    async fn credentials_by_email(&self, email: &str) -> Result<Option<Credentials>, AppError> {
+        let row = sqlx::query(
+            "SELECT id, hash, totp_secret, totp_enabled FROM auths WHERE LOWER(email) = LOWER($1)",
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(credentials))
+    }
+
+    async fn insert_auth(&self, auth: &Auth) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO auths (id, team, email, hash) VALUES ($1, $2, $3, $4)")
+            .bind(auth.id)
+            .bind(auth.team)
+            .bind(&auth.email)
+            .bind(&auth.hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_email(&self, id: &str, email: &str) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE auths SET email = $1 WHERE id = $2")
+            .bind(email)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_hash(&self, id: &str, hash: &str) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE auths SET hash = $1 WHERE id = $2")
+            .bind(hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_auth(&self, id: &str) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        // Foreign keys cascade to everything else the account owns
+        sqlx::query("DELETE FROM auths WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn users(&self) -> Result<Vec<User>, AppError> {
+        let rows = sqlx::query(
+            "SELECT auths.id, auths.email, auths.role, auths.disabled, teams.id AS team_id, teams.name AS team_name FROM auths LEFT JOIN teams ON auths.team = teams.id ORDER BY auths.email",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(user).collect())
+    }
+
+    async fn set_disabled(&self, id: &str, disabled: bool) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE auths SET disabled = $1 WHERE id = $2")
+            .bind(disabled)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_role(&self, id: &str, role: Role) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE auths SET role = $1 WHERE id = $2")
+            .bind(role.as_str())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn insert_team(&self, team: &Team) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO teams (id, name) VALUES ($1, $2)")
+            .bind(team.id)
+            .bind(&team.name)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn teams(&self) -> Result<Vec<Team>, AppError> {
+        let rows = sqlx::query("SELECT id, name FROM teams ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Team {
+                id: row.get("id"),
+                name: row.get("name"),
+            })
+            .collect())
+    }
+
+    async fn profile(&self, id: &str) -> Result<Option<Profile>, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(None);
+        };
+
+        let row = sqlx::query(
+            "SELECT email, display_name, scroll_speed, font_size, mirror_mode FROM auths WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| Profile {
+            email: row.get("email"),
+            display_name: row.get("display_name"),
+            scroll_speed: row.get("scroll_speed"),
+            font_size: row.get("font_size"),
+            mirror_mode: MirrorMode::from(row.get::<&str, _>("mirror_mode")),
+        }))
+    }
+
+    async fn update_profile(
+        &self,
+        id: &str,
+        request: &ProfileUpdateRequest,
+    ) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        sqlx::query(
+            "UPDATE auths SET display_name = COALESCE($1, display_name), scroll_speed = COALESCE($2, scroll_speed), font_size = COALESCE($3, font_size), mirror_mode = COALESCE($4, mirror_mode) WHERE id = $5",
+        )
+        .bind(request.display_name.as_deref().map(str::trim))
+        .bind(request.scroll_speed)
+        .bind(request.font_size)
+        .bind(request.mirror_mode.map(|mode| mode.as_str()))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn oidc_identity(&self, issuer: &str, subject: &str) -> Result<Option<String>, AppError> {
+        let row =
+            sqlx::query("SELECT auth FROM oidc_identities WHERE issuer = $1 AND subject = $2")
+                .bind(issuer)
+                .bind(subject)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.map(|row| row.get::<Uuid, _>("auth").to_string()))
+    }
+
+    async fn link_oidc_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+        auth: &str,
+    ) -> Result<(), AppError> {
+        let auth = Uuid::parse_str(auth).map_err(AppError::internal)?;
+
+        sqlx::query("INSERT INTO oidc_identities (issuer, subject, auth) VALUES ($1, $2, $3)")
+            .bind(issuer)
+            .bind(subject)
+            .bind(auth)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn insert_session(&self, session: NewSession<'_>) -> Result<(), AppError> {
+        let id = Uuid::parse_str(session.id).map_err(AppError::internal)?;
+        let auth = Uuid::parse_str(session.auth).map_err(AppError::internal)?;
+
+        sqlx::query(
+            "INSERT INTO sessions (id, auth, refresh_token, device_label, user_agent, ip_address) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(id)
+        .bind(auth)
+        .bind(session.refresh_token)
+        .bind(session.device.label)
+        .bind(session.device.user_agent)
+        .bind(session.device.ip_address)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn touch_session(
+        &self,
+        id: &str,
+        auth: &str,
+        refresh_token: &str,
+    ) -> Result<bool, AppError> {
+        let (Ok(id), Ok(auth)) = (Uuid::parse_str(id), Uuid::parse_str(auth)) else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query(
+            "UPDATE sessions SET last_used = now() WHERE id = $1 AND auth = $2 AND refresh_token = $3",
+        )
+        .bind(id)
+        .bind(auth)
+        .bind(refresh_token)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn sessions(&self, auth: &str) -> Result<Vec<Session>, AppError> {
+        let Ok(auth) = Uuid::parse_str(auth) else {
+            return Ok(Vec::new());
+        };
+
+        sqlx::query_as::<_, Session>(
+            "SELECT id::text AS id, device_label, user_agent, ip_address, created_at, last_used FROM sessions WHERE auth = $1 ORDER BY last_used DESC",
+        )
+        .bind(auth)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(AppError::from)
+    }
+
+    async fn session_exists(&self, id: &str, auth: &str) -> Result<bool, AppError> {
+        let (Ok(id), Ok(auth)) = (Uuid::parse_str(id), Uuid::parse_str(auth)) else {
+            return Ok(false);
+        };
+
+        let row = sqlx::query("SELECT id FROM sessions WHERE id = $1 AND auth = $2")
+            .bind(id)
+            .bind(auth)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    async fn delete_session(&self, id: &str, auth: &str) -> Result<bool, AppError> {
+        let (Ok(id), Ok(auth)) = (Uuid::parse_str(id), Uuid::parse_str(auth)) else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query("DELETE FROM sessions WHERE id = $1 AND auth = $2")
+            .bind(id)
+            .bind(auth)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_sessions(&self, auth: &str, keep: Option<&str>) -> Result<(), AppError> {
+        let Ok(auth) = Uuid::parse_str(auth) else {
+            return Ok(());
+        };
+        let keep = keep.and_then(|keep| Uuid::parse_str(keep).ok());
+
+        sqlx::query("DELETE FROM sessions WHERE auth = $1 AND id IS DISTINCT FROM $2")
+            .bind(auth)
+            .bind(keep)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn insert_revoked_token(
+        &self,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2)")
+            .bind(jti)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn revoked_tokens(&self) -> Result<Vec<(String, DateTime<Utc>)>, AppError> {
+        sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await?;
+
+        let rows = sqlx::query("SELECT jti, expires_at FROM revoked_tokens")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("jti"), row.get("expires_at")))
+            .collect())
+    }
+
+    async fn insert_api_key(
+        &self,
+        api_key: &ApiKey,
+        team: &Uuid,
+        hash: &str,
+        created_by: &Uuid,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO api_keys (id, team, name, hash, scopes, created_by) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(api_key.id)
+        .bind(team)
+        .bind(&api_key.name)
+        .bind(hash)
+        .bind(ApiKeyScope::join(&api_key.scopes))
+        .bind(created_by)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn api_keys(&self, team: &Uuid) -> Result<Vec<ApiKey>, AppError> {
+        let rows = sqlx::query(
+            "SELECT id, name, scopes, created_at, last_used FROM api_keys WHERE team = $1 ORDER BY created_at DESC",
+        )
+        .bind(team)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ApiKey {
+                id: row.get("id"),
+                name: row.get("name"),
+                scopes: ApiKeyScope::split(row.get("scopes")),
+                created_at: row.get("created_at"),
+                last_used: row.get("last_used"),
+            })
+            .collect())
+    }
+
+    async fn stored_api_key(&self, id: &Uuid) -> Result<Option<StoredApiKey>, AppError> {
+        let row = sqlx::query("SELECT team, hash, scopes FROM api_keys WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| StoredApiKey {
+            team: row.get("team"),
+            hash: row.get("hash"),
+            scopes: row.get("scopes"),
+        }))
+    }
+
+    async fn touch_api_key(&self, id: &Uuid) -> Result<(), AppError> {
+        sqlx::query("UPDATE api_keys SET last_used = now() WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_api_key(&self, team: &Uuid, id: &str) -> Result<bool, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = $1 AND team = $2")
+            .bind(id)
+            .bind(team)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_totp_secret(&self, id: &str, secret: &str) -> Result<(), AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(());
+        };
+
+        sqlx::query("UPDATE auths SET totp_secret = $1 WHERE id = $2")
+            .bind(secret)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn enable_totp(&self, id: &str, recovery_code_hashes: &[String]) -> Result<(), AppError> {
+        let id = Uuid::parse_str(id).map_err(AppError::internal)?;
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("UPDATE auths SET totp_enabled = TRUE WHERE id = $1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE auth = $1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        for hash in recovery_code_hashes {
+            sqlx::query("INSERT INTO recovery_codes (id, auth, hash) VALUES ($1, $2, $3)")
+                .bind(Uuid::new_v4())
+                .bind(id)
+                .bind(hash)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn unused_recovery_codes(&self, auth: &str) -> Result<Vec<(String, String)>, AppError> {
+        let Ok(auth) = Uuid::parse_str(auth) else {
+            return Ok(Vec::new());
+        };
+
+        let rows =
+            sqlx::query("SELECT id, hash FROM recovery_codes WHERE auth = $1 AND used_at IS NULL")
+                .bind(auth)
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get::<Uuid, _>("id").to_string(), row.get("hash")))
+            .collect())
+    }
+
+    async fn use_totp_step(&self, id: &str, step: i64) -> Result<bool, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query(
+            "UPDATE auths SET totp_last_step = $1 WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)",
+        )
+        .bind(step)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_recovery_code(&self, id: &str) -> Result<bool, AppError> {
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = now() WHERE id = $1 AND used_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn save_broadcast(&self, broadcast: &StoredBroadcast) -> Result<(), AppError> {
+        let state = &broadcast.state;
+
+        sqlx::query(
+            "INSERT INTO broadcasts (id, owner, team, speed, scrolling, position, timer, script, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, now()) ON CONFLICT (id) DO UPDATE SET speed = $4, scrolling = $5, position = $6, timer = $7, script = $8, updated_at = now()",
+        )
+        .bind(broadcast.id)
+        .bind(broadcast.owner)
+        .bind(broadcast.team)
+        .bind(state.speed)
+        .bind(state.scrolling)
+        .bind(state.position)
+        .bind(state.timer.as_deref())
+        .bind(state.script.as_deref())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_broadcast(&self, id: &Uuid) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM broadcasts WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn broadcast(&self, id: &Uuid) -> Result<Option<StoredBroadcast>, AppError> {
+        let row = sqlx::query(
+            "SELECT id, owner, team, script, speed, scrolling, position, timer, controller_instance, lease_expires_at FROM broadcasts WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(stored_broadcast))
+    }
+
+    async fn broadcasts(&self, cutoff: DateTime<Utc>) -> Result<Vec<StoredBroadcast>, AppError> {
+        sqlx::query("DELETE FROM broadcasts WHERE updated_at IS NULL OR updated_at < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+
+        let rows = sqlx::query(
+            "SELECT id, owner, team, script, speed, scrolling, position, timer, controller_instance, lease_expires_at FROM broadcasts",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(stored_broadcast).collect())
+    }
+
+    async fn claim_broadcast(
+        &self,
+        id: &Uuid,
+        instance: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE broadcasts SET controller_instance = $2, lease_expires_at = $3 WHERE id = $1 AND (controller_instance IS NULL OR controller_instance = $2 OR lease_expires_at IS NULL OR lease_expires_at < now())",
+        )
+        .bind(id)
+        .bind(instance)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn release_broadcast(&self, id: &Uuid, instance: &str) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE broadcasts SET controller_instance = NULL, lease_expires_at = NULL WHERE id = $1 AND controller_instance = $2",
+        )
+        .bind(id)
+        .bind(instance)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
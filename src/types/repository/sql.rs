@@ -0,0 +1,862 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    mysql::{MySqlPoolOptions, MySqlRow},
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
+    MySql, Pool, Row, Sqlite,
+};
+use uuid::Uuid;
+
+use super::{
+    nothing_to_baseline, pending, ControllerLease, Credentials, NewSession, Repository,
+    StoredApiKey, StoredBroadcast,
+};
+use crate::types::{
+    api_key::{ApiKey, ApiKeyScope},
+    auth::{Auth, Role, Team, User},
+    broadcast::BroadcastState,
+    error::AppError,
+    profile::{MirrorMode, Profile, ProfileUpdateRequest},
+    session::Session,
+};
+
+static MYSQL_MIGRATIONS: Migrator = sqlx::migrate!("./migrations/mysql");
+static SQLITE_MIGRATIONS: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// The MySQL migration that creates what `schemas.sql` used to.
+const INITIAL_MIGRATION: i64 = 20240401000000;
+
+/// Every table and column `schemas.sql` last created, which the initial migration creates too.
+const LEGACY_SCHEMA: &[(&str, &[&str])] = &[
+    ("teams", &["id", "name", "created_at", "last_update"]),
+    (
+        "auths",
+        &[
+            "id",
+            "team",
+            "email",
+            "hash",
+            "role",
+            "totp_secret",
+            "totp_enabled",
+            "display_name",
+            "scroll_speed",
+            "font_size",
+            "mirror_mode",
+            "created_at",
+            "last_update",
+        ],
+    ),
+    (
+        "sessions",
+        &[
+            "id",
+            "auth",
+            "refresh_token",
+            "device_label",
+            "user_agent",
+            "ip_address",
+            "created_at",
+            "last_used",
+        ],
+    ),
+    ("revoked_tokens", &["jti", "expires_at"]),
+    (
+        "api_keys",
+        &[
+            "id",
+            "team",
+            "name",
+            "hash",
+            "scopes",
+            "created_by",
+            "created_at",
+            "last_used",
+        ],
+    ),
+    ("recovery_codes", &["id", "auth", "hash", "used_at"]),
+    (
+        "oidc_identities",
+        &["issuer", "subject", "auth", "created_at"],
+    ),
+];
+
+#[derive(Debug)]
+pub struct MySqlRepository {
+    pool: Pool<MySql>,
+}
+
+impl MySqlRepository {
+    pub async fn connect(database_url: &str) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            pool: MySqlPoolOptions::new().connect(database_url).await?,
+        })
+    }
+}
+
+impl MySqlRepository {
+    async fn adopt_legacy_schema(&self) -> Result<(), AppError> {
+        let mut connection = self.pool.acquire().await?;
+        connection
+            .ensure_migrations_table()
+            .await
+            .map_err(AppError::internal)?;
+        let applied = connection
+            .list_applied_migrations()
+            .await
+            .map_err(AppError::internal)?;
+        if !applied.is_empty() {
+            return Err(AppError::Conflict(
+                "This database's migrations are already tracked".to_string(),
+            ));
+        }
+
+        // information_schema names come back as binary strings on some servers
+        let columns: Vec<(String, String)> = sqlx::query_as(
+            "SELECT CAST(TABLE_NAME AS CHAR), CAST(COLUMN_NAME AS CHAR) FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE()",
+        )
+        .fetch_all(&mut *connection)
+        .await?;
+        let missing: Vec<String> = LEGACY_SCHEMA
+            .iter()
+            .flat_map(|(table, names)| names.iter().map(move |name| (*table, *name)))
+            .filter(|(table, name)| {
+                !columns.iter().any(|(found_table, found_name)| {
+                    found_table.eq_ignore_ascii_case(table) && found_name.eq_ignore_ascii_case(name)
+                })
+            })
+            .map(|(table, name)| format!("{table}.{name}"))
+            .collect();
+        if !missing.is_empty() {
+            return Err(AppError::Validation(format!(
+                "This database doesn't match schemas.sql. Missing: {}",
+                missing.join(", ")
+            )));
+        }
+
+        let Some(initial) = MYSQL_MIGRATIONS
+            .iter()
+            .find(|migration| migration.version == INITIAL_MIGRATION)
+        else {
+            return Err(AppError::Internal(
+                "The initial migration isn't embedded".to_string(),
+            ));
+        };
+
+        // schemas.sql sized refresh tokens at VARCHAR(300), which signed tokens overflow
+        sqlx::query("ALTER TABLE sessions MODIFY refresh_token TEXT NOT NULL")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (?, ?, TRUE, ?, 0)",
+        )
+        .bind(initial.version)
+        .bind(initial.description.as_ref())
+        .bind(initial.checksum.as_ref())
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct SqliteRepository {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteRepository {
+    pub async fn connect(database_url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+
+        // Every connection to an in-memory database gets its own empty copy, so keep exactly one
+        let in_memory = database_url.contains(":memory:") || database_url.contains("mode=memory");
+        let pool = if in_memory {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await?
+        } else {
+            SqlitePoolOptions::new().connect_with(options).await?
+        };
+
+        Ok(Self { pool })
+    }
+
+    async fn adopt_legacy_schema(&self) -> Result<(), AppError> {
+        Err(nothing_to_baseline())
+    }
+}
+
+/// MySQL and SQLite share the same SQL, so both repositories are generated from one body.
+macro_rules! sql_repository {
+    ($repository:ty, $row:ty, $migrations:expr) => {
+        impl $repository {
+            /// Reads a row of `auths` joined with its team as `team_id` and `team_name`.
+            fn user(row: &$row) -> Result<User, AppError> {
+                let id = Uuid::parse_str(row.get("id")).map_err(AppError::internal)?;
+                let team_id: Option<&str> = row.get("team_id");
+
+                Ok(User {
+                    id,
+                    email: row.get("email"),
+                    role: Role::from(row.get::<&str, _>("role")),
+                    disabled: row.get("disabled"),
+                    team: team_id
+                        .and_then(|team_id| Uuid::parse_str(team_id).ok())
+                        .map(|team_id| Team {
+                            id: team_id,
+                            name: row.get("team_name"),
+                        }),
+                })
+            }
+
+            /// Rows with an unreadable id are skipped rather than failing the whole read.
+            fn stored_broadcast(row: &$row) -> Option<StoredBroadcast> {
+                let uuid = |value: Option<&str>| value.and_then(|value| Uuid::parse_str(value).ok());
+
+                Some(StoredBroadcast {
+                    id: uuid(row.get("id"))?,
+                    owner: uuid(row.get("owner")),
+                    team: uuid(row.get("team")),
+                    state: BroadcastState {
+                        script: row.get("script"),
+                        speed: row.get("speed"),
+                        scrolling: row.get("scrolling"),
+                        position: row.get("position"),
+                        timer: row.get("timer"),
+                    },
+                    lease: row
+                        .get::<Option<String>, _>("controller_instance")
+                        .zip(row.get("lease_expires_at"))
+                        .map(|(instance, expires_at)| ControllerLease {
+                            instance,
+                            expires_at,
+                        }),
+                })
+            }
+        }
+
+        #[async_trait]
+        impl Repository for $repository {
+            async fn migrate(&self) -> Result<(), AppError> {
+                $migrations
+                    .run(&self.pool)
+                    .await
+                    .map_err(AppError::internal)
+            }
+
+            async fn ping(&self) -> Result<(), AppError> {
+                sqlx::query("SELECT 1").execute(&self.pool).await?;
+                Ok(())
+            }
+
+            async fn pending_migrations(&self) -> Result<usize, AppError> {
+                // A database that was never migrated has no table to read; `ping` covers it being down
+                let applied: Vec<i64> =
+                    sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+                        .fetch_all(&self.pool)
+                        .await
+                .unwrap_or_default();
+
+                Ok(pending(&$migrations, &applied))
+            }
+
+            async fn baseline(&self) -> Result<(), AppError> {
+                self.adopt_legacy_schema().await
+            }
+
+            async fn user(&self, id: &str) -> Result<Option<User>, AppError> {
+                let row = sqlx::query(
+                    "SELECT auths.id, auths.email, auths.role, auths.disabled, teams.id AS team_id, teams.name AS team_name FROM auths LEFT JOIN teams ON auths.team = teams.id WHERE auths.id = ?",
+                )
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+                row.as_ref().map(Self::user).transpose()
+            }
+
+            async fn credentials(&self, id: &str) -> Result<Option<Credentials>, AppError> {
+                let row = sqlx::query(
+                    "SELECT id, hash, totp_secret, totp_enabled FROM auths WHERE id = ?",
+                )
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+                Ok(row.map(|row| Credentials {
+                    id: row.get("id"),
+                    hash: row.get("hash"),
+                    totp_secret: row.get("totp_secret"),
+                    totp_enabled: row.get("totp_enabled"),
+                }))
+            }
+
+            async fn credentials_by_email(
+                &self,
+                email: &str,
+            ) -> Result<Option<Credentials>, AppError> {
+                let row = sqlx::query(
+                    "SELECT id, hash, totp_secret, totp_enabled FROM auths WHERE email = ?",
+                )
+                .bind(email)
+                .fetch_optional(&self.pool)
+                .await?;
+
+                Ok(row.map(|row| Credentials {
+                    id: row.get("id"),
+                    hash: row.get("hash"),
+                    totp_secret: row.get("totp_secret"),
+                    totp_enabled: row.get("totp_enabled"),
+                }))
+            }
+
+            async fn insert_auth(&self, auth: &Auth) -> Result<(), AppError> {
+                sqlx::query("INSERT INTO auths (id, team, email, hash) VALUES (?, ?, ?, ?)")
+                    .bind(auth.id.to_string())
+                    .bind(auth.team.map(|team| team.to_string()))
+                    .bind(&auth.email)
+                    .bind(&auth.hash)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn update_email(&self, id: &str, email: &str) -> Result<(), AppError> {
+                sqlx::query("UPDATE auths SET email = ? WHERE id = ?")
+                    .bind(email)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn update_hash(&self, id: &str, hash: &str) -> Result<(), AppError> {
+                sqlx::query("UPDATE auths SET hash = ? WHERE id = ?")
+                    .bind(hash)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn delete_auth(&self, id: &str) -> Result<(), AppError> {
+                // Foreign keys cascade to everything else the account owns
+                sqlx::query("DELETE FROM auths WHERE id = ?")
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn users(&self) -> Result<Vec<User>, AppError> {
+                let rows = sqlx::query(
+                    "SELECT auths.id, auths.email, auths.role, auths.disabled, teams.id AS team_id, teams.name AS team_name FROM auths LEFT JOIN teams ON auths.team = teams.id ORDER BY auths.email",
+                )
+                .fetch_all(&self.pool)
+                .await?;
+
+                rows.iter().map(Self::user).collect()
+            }
+
+            async fn set_disabled(&self, id: &str, disabled: bool) -> Result<(), AppError> {
+                sqlx::query("UPDATE auths SET disabled = ? WHERE id = ?")
+                    .bind(disabled)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn update_role(&self, id: &str, role: Role) -> Result<(), AppError> {
+                sqlx::query("UPDATE auths SET role = ? WHERE id = ?")
+                    .bind(role.as_str())
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn insert_team(&self, team: &Team) -> Result<(), AppError> {
+                sqlx::query("INSERT INTO teams (id, name) VALUES (?, ?)")
+                    .bind(team.id.to_string())
+                    .bind(&team.name)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn teams(&self) -> Result<Vec<Team>, AppError> {
+                let rows = sqlx::query("SELECT id, name FROM teams ORDER BY name")
+                    .fetch_all(&self.pool)
+                    .await?;
+
+                rows.iter()
+                    .map(|row| {
+                        Ok(Team {
+                            id: Uuid::parse_str(row.get("id")).map_err(AppError::internal)?,
+                            name: row.get("name"),
+                        })
+                    })
+                    .collect()
+            }
+
+            async fn profile(&self, id: &str) -> Result<Option<Profile>, AppError> {
+                let row = sqlx::query(
+                    "SELECT email, display_name, scroll_speed, font_size, mirror_mode FROM auths WHERE id = ?",
+                )
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+                Ok(row.map(|row| Profile {
+                    email: row.get("email"),
+                    display_name: row.get("display_name"),
+                    scroll_speed: row.get("scroll_speed"),
+                    font_size: row.get("font_size"),
+                    mirror_mode: MirrorMode::from(row.get::<&str, _>("mirror_mode")),
+                }))
+            }
+
+            async fn update_profile(
+                &self,
+                id: &str,
+                request: &ProfileUpdateRequest,
+            ) -> Result<(), AppError> {
+                sqlx::query(
+                    "UPDATE auths SET display_name = COALESCE(?, display_name), scroll_speed = COALESCE(?, scroll_speed), font_size = COALESCE(?, font_size), mirror_mode = COALESCE(?, mirror_mode) WHERE id = ?",
+                )
+                .bind(request.display_name.as_deref().map(str::trim))
+                .bind(request.scroll_speed)
+                .bind(request.font_size)
+                .bind(request.mirror_mode.map(|mode| mode.as_str()))
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            }
+
+            async fn oidc_identity(
+                &self,
+                issuer: &str,
+                subject: &str,
+            ) -> Result<Option<String>, AppError> {
+                let row =
+                    sqlx::query("SELECT auth FROM oidc_identities WHERE issuer = ? AND subject = ?")
+                        .bind(issuer)
+                        .bind(subject)
+                        .fetch_optional(&self.pool)
+                        .await?;
+
+                Ok(row.map(|row| row.get("auth")))
+            }
+
+            async fn link_oidc_identity(
+                &self,
+                issuer: &str,
+                subject: &str,
+                auth: &str,
+            ) -> Result<(), AppError> {
+                sqlx::query("INSERT INTO oidc_identities (issuer, subject, auth) VALUES (?, ?, ?)")
+                    .bind(issuer)
+                    .bind(subject)
+                    .bind(auth)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn insert_session(&self, session: NewSession<'_>) -> Result<(), AppError> {
+                sqlx::query(
+                    "INSERT INTO sessions (id, auth, refresh_token, device_label, user_agent, ip_address) VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(session.id)
+                .bind(session.auth)
+                .bind(session.refresh_token)
+                .bind(session.device.label)
+                .bind(session.device.user_agent)
+                .bind(session.device.ip_address)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            }
+
+            async fn touch_session(
+                &self,
+                id: &str,
+                auth: &str,
+                refresh_token: &str,
+            ) -> Result<bool, AppError> {
+                let result = sqlx::query(
+                    "UPDATE sessions SET last_used = CURRENT_TIMESTAMP WHERE id = ? AND auth = ? AND refresh_token = ?",
+                )
+                .bind(id)
+                .bind(auth)
+                .bind(refresh_token)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn sessions(&self, auth: &str) -> Result<Vec<Session>, AppError> {
+                sqlx::query_as::<_, Session>(
+                    "SELECT id, device_label, user_agent, ip_address, created_at, last_used FROM sessions WHERE auth = ? ORDER BY last_used DESC",
+                )
+                .bind(auth)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(AppError::from)
+            }
+
+            async fn session_exists(&self, id: &str, auth: &str) -> Result<bool, AppError> {
+                let row = sqlx::query("SELECT id FROM sessions WHERE id = ? AND auth = ?")
+                    .bind(id)
+                    .bind(auth)
+                    .fetch_optional(&self.pool)
+                    .await?;
+
+                Ok(row.is_some())
+            }
+
+            async fn delete_session(&self, id: &str, auth: &str) -> Result<bool, AppError> {
+                let result = sqlx::query("DELETE FROM sessions WHERE id = ? AND auth = ?")
+                    .bind(id)
+                    .bind(auth)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn delete_sessions(&self, auth: &str, keep: Option<&str>) -> Result<(), AppError> {
+                sqlx::query("DELETE FROM sessions WHERE auth = ? AND (? IS NULL OR id <> ?)")
+                    .bind(auth)
+                    .bind(keep)
+                    .bind(keep)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn insert_revoked_token(
+                &self,
+                jti: &str,
+                expires_at: DateTime<Utc>,
+            ) -> Result<(), AppError> {
+                sqlx::query("INSERT INTO revoked_tokens (jti, expires_at) VALUES (?, ?)")
+                    .bind(jti)
+                    .bind(expires_at)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn revoked_tokens(&self) -> Result<Vec<(String, DateTime<Utc>)>, AppError> {
+                // Compare against a bound time so both sides are stored in the same format
+                sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= ?")
+                    .bind(Utc::now())
+                    .execute(&self.pool)
+                    .await?;
+
+                let rows = sqlx::query("SELECT jti, expires_at FROM revoked_tokens")
+                    .fetch_all(&self.pool)
+                    .await?;
+
+                Ok(rows
+                    .iter()
+                    .map(|row| (row.get("jti"), row.get("expires_at")))
+                    .collect())
+            }
+
+            async fn insert_api_key(
+                &self,
+                api_key: &ApiKey,
+                team: &Uuid,
+                hash: &str,
+                created_by: &Uuid,
+            ) -> Result<(), AppError> {
+                sqlx::query(
+                    "INSERT INTO api_keys (id, team, name, hash, scopes, created_by) VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(api_key.id.to_string())
+                .bind(team.to_string())
+                .bind(&api_key.name)
+                .bind(hash)
+                .bind(ApiKeyScope::join(&api_key.scopes))
+                .bind(created_by.to_string())
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            }
+
+            async fn api_keys(&self, team: &Uuid) -> Result<Vec<ApiKey>, AppError> {
+                let rows = sqlx::query(
+                    "SELECT id, name, scopes, created_at, last_used FROM api_keys WHERE team = ? ORDER BY created_at DESC",
+                )
+                .bind(team.to_string())
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(rows
+                    .iter()
+                    .filter_map(|row| {
+                        Some(ApiKey {
+                            id: Uuid::parse_str(row.get("id")).ok()?,
+                            name: row.get("name"),
+                            scopes: ApiKeyScope::split(row.get("scopes")),
+                            created_at: row.get("created_at"),
+                            last_used: row.get("last_used"),
+                        })
+                    })
+                    .collect())
+            }
+
+            async fn stored_api_key(&self, id: &Uuid) -> Result<Option<StoredApiKey>, AppError> {
+                let row = sqlx::query("SELECT team, hash, scopes FROM api_keys WHERE id = ?")
+                    .bind(id.to_string())
+                    .fetch_optional(&self.pool)
+                    .await?;
+
+                let Some(row) = row else {
+                    return Ok(None);
+                };
+
+                Ok(Some(StoredApiKey {
+                    team: Uuid::parse_str(row.get("team")).map_err(AppError::internal)?,
+                    hash: row.get("hash"),
+                    scopes: row.get("scopes"),
+                }))
+            }
+
+            async fn touch_api_key(&self, id: &Uuid) -> Result<(), AppError> {
+                sqlx::query("UPDATE api_keys SET last_used = CURRENT_TIMESTAMP WHERE id = ?")
+                    .bind(id.to_string())
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn delete_api_key(&self, team: &Uuid, id: &str) -> Result<bool, AppError> {
+                let result = sqlx::query("DELETE FROM api_keys WHERE id = ? AND team = ?")
+                    .bind(id)
+                    .bind(team.to_string())
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(result.rows_affected() > 0)
+            }
+
+            async fn set_totp_secret(&self, id: &str, secret: &str) -> Result<(), AppError> {
+                sqlx::query("UPDATE auths SET totp_secret = ? WHERE id = ?")
+                    .bind(secret)
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn enable_totp(
+                &self,
+                id: &str,
+                recovery_code_hashes: &[String],
+            ) -> Result<(), AppError> {
+                let mut transaction = self.pool.begin().await?;
+
+                sqlx::query("UPDATE auths SET totp_enabled = TRUE WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *transaction)
+                    .await?;
+
+                sqlx::query("DELETE FROM recovery_codes WHERE auth = ?")
+                    .bind(id)
+                    .execute(&mut *transaction)
+                    .await?;
+
+                for hash in recovery_code_hashes {
+                    sqlx::query("INSERT INTO recovery_codes (id, auth, hash) VALUES (?, ?, ?)")
+                        .bind(Uuid::new_v4().to_string())
+                        .bind(id)
+                        .bind(hash)
+                        .execute(&mut *transaction)
+                        .await?;
+                }
+
+                transaction.commit().await?;
+                Ok(())
+            }
+
+            async fn unused_recovery_codes(
+                &self,
+                auth: &str,
+            ) -> Result<Vec<(String, String)>, AppError> {
+                let rows = sqlx::query(
+                    "SELECT id, hash FROM recovery_codes WHERE auth = ? AND used_at IS NULL",
+                )
+                .bind(auth)
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(rows
+                    .iter()
+                    .map(|row| (row.get("id"), row.get("hash")))
+                    .collect())
+            }
+
+            async fn use_totp_step(&self, id: &str, step: i64) -> Result<bool, AppError> {
+                let result = sqlx::query(
+                    "UPDATE auths SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+                )
+                .bind(step)
+                .bind(id)
+                .bind(step)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(result.rows_affected() == 1)
+            }
+
+            async fn use_recovery_code(&self, id: &str) -> Result<bool, AppError> {
+                let result = sqlx::query(
+                    "UPDATE recovery_codes SET used_at = CURRENT_TIMESTAMP WHERE id = ? AND used_at IS NULL",
+                )
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(result.rows_affected() == 1)
+            }
+
+            async fn save_broadcast(&self, broadcast: &StoredBroadcast) -> Result<(), AppError> {
+                let state = &broadcast.state;
+
+                // MySQL and SQLite spell upserts differently, so update first and insert if that missed
+                let updated = sqlx::query(
+                    "UPDATE broadcasts SET script = ?, speed = ?, scrolling = ?, position = ?, timer = ?, updated_at = ? WHERE id = ?",
+                )
+                .bind(state.script.as_deref())
+                .bind(state.speed)
+                .bind(state.scrolling)
+                .bind(state.position)
+                .bind(state.timer.as_deref())
+                .bind(Utc::now())
+                .bind(broadcast.id.to_string())
+                .execute(&self.pool)
+                .await?;
+
+                if updated.rows_affected() == 0 {
+                    sqlx::query(
+                        "INSERT INTO broadcasts (id, owner, team, script, speed, scrolling, position, timer, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind(broadcast.id.to_string())
+                    .bind(broadcast.owner.map(|owner| owner.to_string()))
+                    .bind(broadcast.team.map(|team| team.to_string()))
+                    .bind(state.script.as_deref())
+                    .bind(state.speed)
+                    .bind(state.scrolling)
+                    .bind(state.position)
+                    .bind(state.timer.as_deref())
+                    .bind(Utc::now())
+                    .execute(&self.pool)
+                    .await?;
+                }
+
+                Ok(())
+            }
+
+            async fn delete_broadcast(&self, id: &Uuid) -> Result<(), AppError> {
+                sqlx::query("DELETE FROM broadcasts WHERE id = ?")
+                    .bind(id.to_string())
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn broadcast(&self, id: &Uuid) -> Result<Option<StoredBroadcast>, AppError> {
+                let row = sqlx::query(
+                    "SELECT id, owner, team, script, speed, scrolling, position, timer, controller_instance, lease_expires_at FROM broadcasts WHERE id = ?",
+                )
+                .bind(id.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+
+                Ok(row.as_ref().and_then(Self::stored_broadcast))
+            }
+
+            async fn broadcasts(
+                &self,
+                cutoff: DateTime<Utc>,
+            ) -> Result<Vec<StoredBroadcast>, AppError> {
+                sqlx::query("DELETE FROM broadcasts WHERE updated_at IS NULL OR updated_at < ?")
+                    .bind(cutoff)
+                    .execute(&self.pool)
+                    .await?;
+
+                let rows = sqlx::query(
+                    "SELECT id, owner, team, script, speed, scrolling, position, timer, controller_instance, lease_expires_at FROM broadcasts",
+                )
+                .fetch_all(&self.pool)
+                .await?;
+
+                Ok(rows.iter().filter_map(Self::stored_broadcast).collect())
+            }
+
+            async fn claim_broadcast(
+                &self,
+                id: &Uuid,
+                instance: &str,
+                expires_at: DateTime<Utc>,
+            ) -> Result<bool, AppError> {
+                let result = sqlx::query(
+                    "UPDATE broadcasts SET controller_instance = ?, lease_expires_at = ? WHERE id = ? AND (controller_instance IS NULL OR controller_instance = ? OR lease_expires_at IS NULL OR lease_expires_at < ?)",
+                )
+                .bind(instance)
+                .bind(expires_at)
+                .bind(id.to_string())
+                .bind(instance)
+                .bind(Utc::now())
+                .execute(&self.pool)
+                .await?;
+
+                Ok(result.rows_affected() == 1)
+            }
+
+            async fn release_broadcast(&self, id: &Uuid, instance: &str) -> Result<(), AppError> {
+                sqlx::query(
+                    "UPDATE broadcasts SET controller_instance = NULL, lease_expires_at = NULL WHERE id = ? AND controller_instance = ?",
+                )
+                .bind(id.to_string())
+                .bind(instance)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(())
+            }
+        }
+    };
+}
+
+sql_repository!(MySqlRepository, MySqlRow, MYSQL_MIGRATIONS);
+sql_repository!(SqliteRepository, SqliteRow, SQLITE_MIGRATIONS);
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tokio::sync::RwLock;
+
+/// In-memory set of revoked access token ids (`jti`), mirrored in the `revoked_tokens` table.
+/// Entries are kept until the token they refer to would have expired anyway.
+#[derive(Debug, Default)]
+pub struct RevocationList {
+    tokens: RwLock<HashMap<String, usize>>,
+}
+
+impl RevocationList {
+    pub fn new(tokens: HashMap<String, usize>) -> Self {
+        Self {
+            tokens: RwLock::new(tokens),
+        }
+    }
+
+    pub async fn is_revoked(&self, jti: &str) -> bool {
+        self.tokens.read().await.contains_key(jti)
+    }
+
+    pub async fn revoke(&self, jti: &str, exp: usize) {
+        let now = Utc::now().timestamp() as usize;
+        let mut tokens = self.tokens.write().await;
+
+        // Expired tokens are rejected by validation, so there's no need to keep them around
+        tokens.retain(|_, expires| *expires > now);
+        tokens.insert(jti.to_string(), exp);
+    }
+}
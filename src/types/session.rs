@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Details about the device a session was opened from.
+#[derive(Debug, Default, Clone)]
+pub struct SessionDevice {
+    pub label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Session {
+    pub id: String,
+    pub device_label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub current: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SessionsResponse {
+    success: bool,
+    sessions: Vec<Session>,
+}
+
+impl SessionsResponse {
+    pub fn new(sessions: Vec<Session>) -> Self {
+        Self {
+            success: true,
+            sessions,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+/// Login attempts allowed from one address within `IP_WINDOW`.
+const IP_ATTEMPTS: u32 = 10;
+const IP_WINDOW: Duration = Duration::from_secs(60);
+
+/// Consecutive failures an account may have before it's locked.
+const LOCKOUT_THRESHOLD: u32 = 5;
+const LOCKOUT_BASE: Duration = Duration::from_secs(30);
+const LOCKOUT_MAX: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    attempts: u32,
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Throttles login attempts per client address and locks accounts out after repeated failures.
+/// Each lockout past the threshold doubles in length, up to an hour.
+#[derive(Debug, Default)]
+pub struct LoginThrottle {
+    ips: Mutex<HashMap<IpAddr, Window>>,
+    accounts: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginThrottle {
+    /// Records an attempt, returning how long the caller must wait if it isn't allowed.
+    pub async fn check(&self, ip: IpAddr, account: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        {
+            let mut ips = self.ips.lock().await;
+            ips.retain(|_, window| now.duration_since(window.started) < IP_WINDOW);
+
+            let window = ips.entry(ip).or_insert(Window {
+                started: now,
+                attempts: 0,
+            });
+            window.attempts += 1;
+
+            if window.attempts > IP_ATTEMPTS {
+                return Err(IP_WINDOW - now.duration_since(window.started));
+            }
+        }
+
+        let accounts = self.accounts.lock().await;
+        match accounts
+            .get(account)
+            .and_then(|failures| failures.locked_until)
+        {
+            Some(locked_until) if locked_until > now => Err(locked_until - now),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(&self, account: &str) {
+        let now = Instant::now();
+        let mut accounts = self.accounts.lock().await;
+
+        // Forget accounts that have gone quiet for longer than the longest lockout
+        accounts.retain(|_, failures| now.duration_since(failures.last_failure) < LOCKOUT_MAX);
+
+        let failures = accounts.entry(account.to_string()).or_insert(Failures {
+            count: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        failures.count += 1;
+        failures.last_failure = now;
+
+        if failures.count >= LOCKOUT_THRESHOLD {
+            let doublings = (failures.count - LOCKOUT_THRESHOLD).min(16);
+            let lockout = (LOCKOUT_BASE * 2u32.pow(doublings)).min(LOCKOUT_MAX);
+            failures.locked_until = Some(now + lockout);
+        }
+    }
+
+    pub async fn record_success(&self, account: &str) {
+        self.accounts.lock().await.remove(account);
+    }
+}
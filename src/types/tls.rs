@@ -0,0 +1,49 @@
+use std::{io, path::Path, time::SystemTime};
+
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::TlsConfig;
+
+/// Reads the certificate chain and key, failing if either is missing or unusable.
+pub async fn load(tls: &TlsConfig) -> io::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await
+}
+
+/// Swaps in renewed certificates without a restart. New connections get them once both files
+/// have changed and load together; a half-written pair is retried on the next check.
+pub fn watch(tls: TlsConfig, rustls: RustlsConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut loaded = modified(&tls.cert_path, &tls.key_path);
+
+        loop {
+            tokio::time::sleep(tls.reload_interval).await;
+
+            let current = modified(&tls.cert_path, &tls.key_path);
+            if current == loaded {
+                continue;
+            }
+
+            match rustls
+                .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+                .await
+            {
+                Ok(()) => {
+                    info!(cert = %tls.cert_path.display(), "Reloaded TLS certificate");
+                    loaded = current;
+                }
+                Err(err) => warn!(%err, "Couldn't reload TLS certificate, keeping the old one"),
+            }
+        }
+    })
+}
+
+fn modified(cert: &Path, key: &Path) -> Option<(SystemTime, SystemTime)> {
+    let mtime = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    Some((mtime(cert)?, mtime(key)?))
+}
@@ -0,0 +1,148 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::types::error::AppError;
+
+const ISSUER: &str = "LiveScript";
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Seconds each code is valid for.
+const STEP: u64 = 30;
+/// How many steps either side of now a code is still accepted, for clock drift.
+const SKEW: u64 = 1;
+
+pub struct TotpManager;
+
+impl TotpManager {
+    /// Generates a new base32 encoded secret.
+    pub fn generate_secret() -> String {
+        match Secret::generate_secret().to_encoded() {
+            Secret::Encoded(secret) => secret,
+            Secret::Raw(_) => unreachable!("secret was just encoded"),
+        }
+    }
+
+    fn totp(secret: &str, email: &str) -> Option<TOTP> {
+        let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            0,
+            STEP,
+            secret,
+            Some(ISSUER.to_string()),
+            email.to_string(),
+        )
+        .ok()
+    }
+
+    /// The `otpauth://` URI authenticator apps read from a QR code.
+    pub fn provisioning_uri(secret: &str, email: &str) -> Option<String> {
+        Some(Self::totp(secret, email)?.get_url())
+    }
+
+    /// Returns the time step a valid code belongs to, so it can be refused if used again.
+    pub fn verify(secret: &str, code: &str) -> Option<i64> {
+        let totp = Self::totp(secret, "")?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() / STEP;
+        (now.saturating_sub(SKEW)..=now + SKEW)
+            .find(|step| totp.check(code.trim(), step * STEP))
+            .map(|step| step as i64)
+    }
+
+    /// Generates single-use recovery codes, returning them alongside their argon2 hashes.
+    pub fn generate_recovery_codes() -> Result<Vec<(String, String)>, AppError> {
+        (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let code: String = rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(10)
+                    .map(|c| char::from(c).to_ascii_lowercase())
+                    .collect();
+                let code = format!("{}-{}", &code[..5], &code[5..]);
+                let hash = Argon2::default()
+                    .hash_password(code.as_bytes(), &SaltString::generate(&mut OsRng))
+                    .map_err(AppError::internal)?
+                    .to_string();
+                Ok((code, hash))
+            })
+            .collect()
+    }
+
+    pub fn verify_recovery_code(code: &str, hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(hash) else {
+            return false;
+        };
+        Argon2::default()
+            .verify_password(code.trim().to_lowercase().as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TotpLoginRequest {
+    pub mfa_token: String,
+    pub code: String,
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollmentResponse {
+    success: bool,
+    secret: String,
+    provisioning_uri: String,
+}
+
+impl TotpEnrollmentResponse {
+    pub fn new(secret: String, provisioning_uri: String) -> Self {
+        Self {
+            success: true,
+            secret,
+            provisioning_uri,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    success: bool,
+    recovery_codes: Vec<String>,
+}
+
+impl RecoveryCodesResponse {
+    pub fn new(recovery_codes: Vec<String>) -> Self {
+        Self {
+            success: true,
+            recovery_codes,
+        }
+    }
+}
+
+/// Returned in place of tokens when a password was correct but a second factor is needed.
+#[derive(Debug, Serialize)]
+pub struct TotpChallengeResponse {
+    success: bool,
+    totp_required: bool,
+    mfa_token: String,
+}
+
+impl TotpChallengeResponse {
+    pub fn new(mfa_token: String) -> Self {
+        Self {
+            success: true,
+            totp_required: true,
+            mfa_token,
+        }
+    }
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use common::{file_backed_config, TestServer, PASSWORD};
+use livescript::{admin, Config};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+/// Runs a command as `livescript-admin` would, returning what it printed.
+async fn admin(config: &Config, args: &[&str], input: &str) -> Result<String, String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut output = Vec::new();
+
+    admin::run(config, &args, input.as_bytes(), &mut output)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[tokio::test]
+async fn operators_create_teams_and_accounts() {
+    let (config, database) = file_backed_config();
+    admin(&config, &["migrate"], "").await.unwrap();
+
+    let team = admin(&config, &["create-team", "Newsroom"], "")
+        .await
+        .unwrap();
+    let team = team.trim();
+    let password = format!("{PASSWORD}\n");
+    admin(
+        &config,
+        &[
+            "create-user",
+            "anchor@example.com",
+            "--team",
+            team,
+            "--role",
+            "admin",
+        ],
+        &password,
+    )
+    .await
+    .unwrap();
+
+    let teams = admin(&config, &["teams"], "").await.unwrap();
+    assert_eq!(teams, format!("{team}\tNewsroom\n"));
+    let users = admin(&config, &["users"], "").await.unwrap();
+    assert!(users.contains(&format!("anchor@example.com\tadmin\t{team}")));
+
+    // The account works on the server straight away
+    let server = TestServer::start_with(config.clone()).await;
+    let client = server.client();
+    let response = client.login("anchor@example.com", PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Value = client.get("/me").await.json().await.unwrap();
+    assert_eq!(me["profile"]["email"], "anchor@example.com");
+
+    let unknown_team = admin(
+        &config,
+        &[
+            "create-user",
+            "b@example.com",
+            "--team",
+            &uuid::Uuid::new_v4().to_string(),
+        ],
+        &password,
+    )
+    .await;
+    assert!(unknown_team.unwrap_err().contains("Team not found"));
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn resetting_a_password_signs_the_account_out() {
+    let (config, database) = file_backed_config();
+    let server = TestServer::start_with(config.clone()).await;
+    let client = server.client();
+    let email = client.sign_up().await;
+    assert_eq!(
+        client.post("/auth/refresh", json!({})).await.status(),
+        StatusCode::OK
+    );
+
+    admin(&config, &["reset-password", &email], "New!Password1\n")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        client.post("/auth/refresh", json!({})).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let old = server.client().login(&email, PASSWORD).await;
+    assert_eq!(old.status(), StatusCode::UNAUTHORIZED);
+    let new = server.client().login(&email, "New!Password1").await;
+    assert_eq!(new.status(), StatusCode::OK);
+
+    let weak = admin(&config, &["reset-password", &email], "short\n").await;
+    assert!(weak.is_err());
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn revoking_sessions_ends_refresh() {
+    let (config, database) = file_backed_config();
+    let server = TestServer::start_with(config.clone()).await;
+    let client = server.client();
+    let email = client.sign_up().await;
+
+    admin(&config, &["revoke-sessions", &email], "")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        client.post("/auth/refresh", json!({})).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    // Access tokens already issued go too
+    assert_eq!(client.get("/me").await.status(), StatusCode::UNAUTHORIZED);
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn exports_import_into_a_fresh_database() {
+    let (source, source_database) = file_backed_config();
+    admin(&source, &["migrate"], "").await.unwrap();
+    let team = admin(&source, &["create-team", "Studio"], "")
+        .await
+        .unwrap();
+    let password = format!("{PASSWORD}\n");
+    admin(
+        &source,
+        &["create-user", "host@example.com", "--team", team.trim()],
+        &password,
+    )
+    .await
+    .unwrap();
+
+    let export = std::env::temp_dir().join(format!("livescript-{}.json", uuid::Uuid::new_v4()));
+    let export_path = export.to_str().unwrap();
+    // Even over a file others could read, since it holds password hashes
+    std::fs::write(&export, "").unwrap();
+    admin(&source, &["export", export_path], "").await.unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&export).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let (target, target_database) = file_backed_config();
+    admin(&target, &["migrate"], "").await.unwrap();
+    let imported = admin(&target, &["import", export_path], "").await.unwrap();
+    assert_eq!(imported, "Imported 1 teams and 1 accounts\n");
+
+    // Importing again changes nothing
+    let again = admin(&target, &["import", export_path], "").await.unwrap();
+    assert!(again.starts_with("Imported 0 teams and 0 accounts\n"));
+    assert!(again.contains("Skipped host@example.com"));
+
+    // Same password, same team, on the new database
+    assert_eq!(
+        admin(&source, &["users"], "").await.unwrap(),
+        admin(&target, &["users"], "").await.unwrap()
+    );
+    let server = TestServer::start_with(target).await;
+    let client = server.client();
+    let response = client.login("host@example.com", PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Value = client.get("/me").await.json().await.unwrap();
+    assert_eq!(me["profile"]["email"], "host@example.com");
+
+    for path in [source_database, target_database, export] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[tokio::test]
+async fn unknown_commands_print_usage() {
+    let (config, database) = file_backed_config();
+
+    let err = admin(&config, &["frobnicate"], "").await.unwrap_err();
+    assert!(err.starts_with("Usage: livescript-admin"));
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn only_mysql_databases_are_baselined() {
+    let (config, database) = file_backed_config();
+
+    let err = admin(&config, &["baseline"], "").await.unwrap_err();
+    assert!(err.contains("Only MySQL databases were built from schemas.sql"));
+
+    let _ = std::fs::remove_file(database);
+}
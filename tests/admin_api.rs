@@ -0,0 +1,228 @@
+mod common;
+
+use std::{path::PathBuf, time::Duration};
+
+use common::{file_backed_config, TestClient, TestServer, PASSWORD};
+use livescript::admin;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// A server with a `super_admin` account created the way operators do, and a client signed
+/// in as it.
+async fn operator() -> (TestServer, TestClient, PathBuf) {
+    let (config, database) = file_backed_config();
+    admin::run(&config, &["migrate".to_string()], &b""[..], Vec::new())
+        .await
+        .unwrap();
+    let args = ["create-user", "ops@example.com", "--role", "super_admin"].map(String::from);
+    let password = format!("{PASSWORD}\n");
+    admin::run(&config, &args, password.as_bytes(), Vec::new())
+        .await
+        .unwrap();
+
+    let server = TestServer::start_with(config).await;
+    let client = server.client();
+    let response = client.login("ops@example.com", PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    (server, client, database)
+}
+
+/// The broadcast as listed by the admin API, once it shows `viewers` connected here.
+async fn listed(client: &TestClient, id: &Uuid, viewers: usize) -> Value {
+    for _ in 0..50 {
+        let body: Value = client.get("/admin/broadcasts").await.json().await.unwrap();
+        let broadcast = body["broadcasts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|broadcast| broadcast["id"] == id.to_string())
+            .cloned();
+        if let Some(broadcast) = broadcast {
+            if broadcast["viewers"].as_array().unwrap().len() == viewers {
+                return broadcast;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("broadcast {id} never listed with {viewers} viewers");
+}
+
+#[tokio::test]
+async fn only_super_admins_reach_the_admin_api() {
+    let (server, _, database) = operator().await;
+
+    let anonymous = server.client();
+    let response = anonymous.get("/admin/broadcasts").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let member = server.client();
+    member.sign_up().await;
+    for path in ["/admin/broadcasts", "/admin/auth-failures"] {
+        let response = member.get(path).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+    let response = member
+        .post(
+            &format!("/admin/broadcasts/{}/end", Uuid::new_v4()),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn operators_see_and_end_any_broadcast() {
+    let (server, operator, database) = operator().await;
+    let host = server.client();
+    host.sign_up().await;
+    let (mut controller, id) = host.start_broadcast().await;
+    let mut viewer = server.client().join_broadcast(&id.to_string()).await;
+
+    let broadcast = listed(&operator, &id, 1).await;
+    assert_eq!(broadcast["controlled_here"], true);
+
+    let response = operator
+        .post(&format!("/admin/broadcasts/{id}/end"), json!({}))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_eq!(viewer.recv().await, "state:end");
+    assert!(viewer.closed().await);
+    assert_eq!(controller.recv().await, "state:end");
+    assert!(controller.closed().await);
+
+    // It's gone for good, so it can't be ended twice or resumed
+    let response = operator
+        .post(&format!("/admin/broadcasts/{id}/end"), json!({}))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let resume = host
+        .websocket(&format!("/broadcast/init?resume={id}"))
+        .await;
+    assert_eq!(resume.err(), Some(StatusCode::NOT_FOUND));
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn operators_kick_a_single_viewer() {
+    let (server, operator, database) = operator().await;
+    let host = server.client();
+    host.sign_up().await;
+    let (mut controller, id) = host.start_broadcast().await;
+    let mut viewers = [
+        server.client().join_broadcast(&id.to_string()).await,
+        server.client().join_broadcast(&id.to_string()).await,
+    ];
+
+    let broadcast = listed(&operator, &id, 2).await;
+    let first = broadcast["viewers"][0].as_str().unwrap().to_string();
+    let response = operator
+        .delete(&format!("/admin/broadcasts/{id}/viewers/{first}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Which socket got which address isn't known, so check one went and the other stayed
+    let broadcast = listed(&operator, &id, 1).await;
+    assert_ne!(broadcast["viewers"][0], first.as_str());
+    controller.send("scroll:speed_2").await;
+    let mut received = Vec::new();
+    for viewer in &mut viewers {
+        received.push(viewer.recv().await);
+    }
+    let gone = received
+        .iter()
+        .position(|message| message == "state:kicked")
+        .expect("a viewer was kicked");
+    assert_eq!(received[1 - gone], "scroll:speed_2");
+    assert!(viewers[gone].closed().await);
+    // The kick went out on the broadcast's channel, but only the viewer acted on it
+    assert_eq!(controller.recv().await, "scroll:speed_2");
+
+    // A viewer that isn't here may be on another instance, so the kick is passed on
+    let response = operator
+        .delete(&format!("/admin/broadcasts/{id}/viewers/{first}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let response = operator
+        .delete(&format!(
+            "/admin/broadcasts/{}/viewers/{first}",
+            Uuid::new_v4()
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn disabled_accounts_are_locked_out_until_enabled() {
+    let (server, operator, database) = operator().await;
+    let user = server.client();
+    let email = user.sign_up().await;
+
+    let response = operator
+        .post(&format!("/admin/users/{email}/disable"), json!({}))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The session already open stops working straight away
+    let response = user.get("/me").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "account_disabled");
+    let response = server.client().login(&email, PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = operator
+        .post(&format!("/admin/users/{email}/enable"), json!({}))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = server.client().login(&email, PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = operator
+        .post("/admin/users/ops@example.com/disable", json!({}))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = operator
+        .post(
+            &format!("/admin/users/{}/disable", Uuid::new_v4()),
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn failed_sign_ins_are_listed_newest_first() {
+    let (server, operator, database) = operator().await;
+    let client = server.client();
+    let email = client.sign_up().await;
+
+    server.client().login(&email, "Wrong!Password1").await;
+    server.client().login("nobody@example.com", PASSWORD).await;
+
+    let body: Value = operator
+        .get("/admin/auth-failures")
+        .await
+        .json()
+        .await
+        .unwrap();
+    let failures = body["failures"].as_array().unwrap();
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0]["account"], "nobody@example.com");
+    assert_eq!(failures[1]["account"], email.as_str());
+    for failure in failures {
+        assert_eq!(failure["method"], "password");
+        assert_eq!(failure["reason"], "invalid_credentials");
+        assert_eq!(failure["ip"], "127.0.0.1");
+    }
+
+    let _ = std::fs::remove_file(database);
+}
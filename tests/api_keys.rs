@@ -0,0 +1,83 @@
+mod common;
+
+use std::path::PathBuf;
+
+use common::{file_backed_config, TestClient, TestServer, PASSWORD};
+use livescript::{admin, Config};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+/// Runs a command as `livescript-admin` would, returning what it printed.
+async fn admin(config: &Config, args: &[&str], input: &str) -> String {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut output = Vec::new();
+
+    admin::run(config, &args, input.as_bytes(), &mut output)
+        .await
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+/// A server with one team, and clients signed in as its admin and as one of its members.
+async fn team() -> (TestServer, TestClient, TestClient, PathBuf) {
+    let (config, database) = file_backed_config();
+    admin(&config, &["migrate"], "").await;
+    let team = admin(&config, &["create-team", "Newsroom"], "").await;
+    let team = team.trim();
+    let password = format!("{PASSWORD}\n");
+    admin(
+        &config,
+        &[
+            "create-user",
+            "editor@example.com",
+            "--team",
+            team,
+            "--role",
+            "admin",
+        ],
+        &password,
+    )
+    .await;
+
+    let server = TestServer::start_with(config).await;
+    let editor = server.client();
+    let response = editor.login("editor@example.com", PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let member = server.client();
+    let response = member
+        .post(
+            "/auth/register",
+            json!({ "email": "reporter@example.com", "password": PASSWORD, "team": team }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    (server, editor, member, database)
+}
+
+#[tokio::test]
+async fn only_team_admins_manage_api_keys() {
+    let (_server, editor, member, database) = team().await;
+    let request = json!({ "name": "Studio desk", "scopes": ["broadcast:control"] });
+
+    let response = member.post("/api-keys", request.clone()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "forbidden");
+    assert_eq!(
+        member.get("/api-keys").await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let response = editor.post("/api-keys", request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    let id = created["api_key"]["id"].as_str().unwrap();
+
+    let path = format!("/api-keys/{id}");
+    assert_eq!(member.delete(&path).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(editor.delete(&path).await.status(), StatusCode::OK);
+
+    let _ = std::fs::remove_file(database);
+}
@@ -0,0 +1,472 @@
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::{file_backed_config, TestClient, TestServer, PASSWORD};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::task::JoinSet;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn registration_signs_the_user_in() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let email = client.sign_up().await;
+
+    assert!(client.cookie("lat").is_some());
+    assert!(client.cookie("lrt").is_some());
+
+    let response = client.get("/me").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: Value = response.json().await.unwrap();
+    assert_eq!(profile["profile"]["email"], email.as_str());
+}
+
+#[tokio::test]
+async fn registering_an_existing_email_conflicts() {
+    let server = TestServer::start().await;
+    let email = server.client().sign_up().await;
+
+    let response = server.client().register(&email.to_uppercase()).await;
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "user_exists");
+}
+
+#[tokio::test]
+async fn racing_registrations_for_one_email_conflict() {
+    let (config, database) = file_backed_config();
+    let server = TestServer::start_with(config).await;
+
+    // Some of these pass the email check together, and only the unique index stops them
+    let mut registrations = JoinSet::new();
+    for _ in 0..8 {
+        let client = server.client();
+        registrations.spawn(async move { client.register("race@example.com").await.status() });
+    }
+    let mut statuses = Vec::new();
+    while let Some(status) = registrations.join_next().await {
+        statuses.push(status.unwrap());
+    }
+
+    assert_eq!(
+        statuses
+            .iter()
+            .filter(|s| **s == StatusCode::CREATED)
+            .count(),
+        1
+    );
+    assert!(statuses
+        .iter()
+        .all(|s| *s == StatusCode::CREATED || *s == StatusCode::CONFLICT));
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn registering_into_an_unknown_team_is_invalid() {
+    let server = TestServer::start().await;
+
+    let response = server
+        .client()
+        .post(
+            "/auth/register",
+            json!({
+                "email": "someone@example.com",
+                "password": PASSWORD,
+                "team": Uuid::new_v4(),
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "validation_failed");
+}
+
+#[tokio::test]
+async fn unreadable_requests_get_json_errors() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let missing_field = client
+        .post("/auth/login", json!({ "email": "someone@example.com" }))
+        .await;
+    assert_eq!(missing_field.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = missing_field.json().await.unwrap();
+    assert_eq!(body["success"], false);
+    assert_eq!(body["code"], "malformed_request");
+
+    let not_json = reqwest::Client::new()
+        .post(client.url("/auth/login"))
+        .header("content-type", "application/json")
+        .body("{")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(not_json.status(), StatusCode::BAD_REQUEST);
+    let body: Value = not_json.json().await.unwrap();
+    assert_eq!(body["code"], "malformed_request");
+}
+
+#[tokio::test]
+async fn login_accepts_the_right_password_only() {
+    let server = TestServer::start().await;
+    let email = server.client().sign_up().await;
+    let client = server.client();
+
+    let response = client.login(&email, "Wrong!Password9").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_credentials");
+    assert!(client.cookie("lat").is_none());
+
+    let response = client.login(&email, PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(client.get("/me").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn protected_routes_need_a_token() {
+    let server = TestServer::start().await;
+
+    let response = server.client().get("/me").await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "missing_token");
+}
+
+#[tokio::test]
+async fn refresh_issues_a_new_access_token() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    client.sign_up().await;
+    let access_token = client.cookie("lat").unwrap();
+
+    // Tokens minted within the same second would otherwise be identical
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let response = client.post("/auth/refresh", json!({})).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["success"], true);
+    assert_ne!(client.cookie("lat").unwrap(), access_token);
+    assert_eq!(client.get("/me").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn logout_ends_the_session() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    client.sign_up().await;
+    let access_token = client.cookie("lat").unwrap();
+
+    // Another site can't sign the user out behind their back
+    let forged = reqwest::Client::new()
+        .post(client.url("/auth/logout"))
+        .header("cookie", format!("lat={access_token}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(forged.status(), StatusCode::FORBIDDEN);
+
+    assert_eq!(
+        client.post("/auth/logout", json!({})).await.status(),
+        StatusCode::OK
+    );
+    assert!(client.cookie("lat").is_none());
+
+    // Replaying the old token fails too, since it was revoked rather than just forgotten
+    let response = reqwest::Client::new()
+        .get(client.url("/me"))
+        .header("cookie", format!("lat={access_token}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn revoking_a_session_ends_its_access_token() {
+    let server = TestServer::start().await;
+    let laptop = server.client();
+    let email = laptop.sign_up().await;
+    let phone = server.client();
+    phone.login(&email, PASSWORD).await;
+    let phone_token = phone.cookie("lat").unwrap();
+
+    let sessions: Value = laptop.get("/auth/sessions").await.json().await.unwrap();
+    let phone_session = sessions["sessions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|session| session["current"] == false)
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = laptop
+        .delete(&format!("/auth/sessions/{phone_session}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The token was issued before the revocation, and is refused all the same
+    let response = reqwest::Client::new()
+        .get(phone.url("/me"))
+        .bearer_auth(&phone_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "revoked_token");
+    assert_eq!(laptop.get("/me").await.status(), StatusCode::OK);
+}
+
+/// The code an authenticator app would show for `secret`, `steps` periods from now. Each code
+/// only works once, so a login straight after another needs the next one.
+fn totp_code(secret: &str, steps: u64) -> String {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, None, String::new()).unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    totp.generate(now.as_secs() + steps * 30)
+}
+
+/// Turns on two-factor for the signed-in account, returning its secret and recovery codes.
+async fn enable_two_factor(client: &TestClient) -> (String, Vec<String>) {
+    let response = client.post("/auth/totp/enroll", json!({})).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let enrollment: Value = response.json().await.unwrap();
+    let secret = enrollment["secret"].as_str().unwrap().to_string();
+    assert!(enrollment["provisioning_uri"]
+        .as_str()
+        .unwrap()
+        .starts_with("otpauth://totp/"));
+
+    let response = client
+        .post("/auth/totp/confirm", json!({ "code": "000000" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .post(
+            "/auth/totp/confirm",
+            json!({ "code": totp_code(&secret, 0) }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let confirmed: Value = response.json().await.unwrap();
+    let recovery_codes = confirmed["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+
+    (secret, recovery_codes)
+}
+
+/// Logs in with the password, returning the MFA token the second step needs.
+async fn password_step(client: &TestClient, email: &str) -> String {
+    let response = client.login(email, PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["totp_required"], true);
+    body["mfa_token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn two_factor_logins_need_a_current_code() {
+    let server = TestServer::start().await;
+    let enrolled = server.client();
+    let email = enrolled.sign_up().await;
+    let (secret, recovery_codes) = enable_two_factor(&enrolled).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    // The password alone only gets as far as the challenge
+    let client = server.client();
+    let mfa_token = password_step(&client, &email).await;
+    assert!(client.cookie("lat").is_none());
+
+    let response = client
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": "000000" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_totp_code");
+    assert!(client.cookie("lat").is_none());
+
+    let code = totp_code(&secret, 1);
+    let response = client
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": code }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(client.get("/me").await.status(), StatusCode::OK);
+
+    // Neither the token nor the code gets a second session
+    let response = server
+        .client()
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": recovery_codes[0] }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "session_expired");
+
+    let again = server.client();
+    let mfa_token = password_step(&again, &email).await;
+    let response = again
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": code }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_totp_code");
+
+    // An MFA token is no access token
+    let response = reqwest::Client::new()
+        .get(client.url("/me"))
+        .bearer_auth(&mfa_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn recovery_codes_work_once() {
+    let server = TestServer::start().await;
+    let enrolled = server.client();
+    let email = enrolled.sign_up().await;
+    let (_, recovery_codes) = enable_two_factor(&enrolled).await;
+    let recovery_code = &recovery_codes[0];
+
+    let client = server.client();
+    let mfa_token = password_step(&client, &email).await;
+    let response = client
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": recovery_code.to_uppercase() }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let again = server.client();
+    let mfa_token = password_step(&again, &email).await;
+    let response = again
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": recovery_code }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_totp_code");
+
+    // The others are still good
+    let response = again
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": mfa_token, "code": recovery_codes[1] }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn repeated_wrong_passwords_lock_the_account() {
+    let server = TestServer::start().await;
+    let email = server.client().sign_up().await;
+    let client = server.client();
+
+    for _ in 0..5 {
+        let response = client.login(&email, "Wrong!Password9").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // Even the right password has to wait now
+    let response = client.login(&email, PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=30).contains(&retry_after));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "rate_limited");
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    let server = TestServer::start().await;
+    let email = server.client().sign_up().await;
+    let client = server.client();
+
+    for _ in 0..2 {
+        for _ in 0..4 {
+            let response = client.login(&email, "Wrong!Password9").await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = client.login(&email, PASSWORD).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn one_address_can_only_try_so_many_accounts() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    for attempt in 0..10 {
+        let response = client
+            .login(&format!("guess{attempt}@example.com"), PASSWORD)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let response = client.login("guess10@example.com", PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+}
+
+#[tokio::test]
+async fn rechecking_the_password_shares_the_login_lockout() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let email = client.sign_up().await;
+    let wrong = json!({ "current_password": "Wrong!Password9", "new_password": "New!Password99" });
+
+    for _ in 0..5 {
+        let response = client.put("/me/password", wrong.clone()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["code"], "incorrect_password");
+    }
+
+    let response = client
+        .put(
+            "/me/email",
+            json!({ "email": "new@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        server.client().login(&email, PASSWORD).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{config, TestServer};
+use livescript::Config;
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn starting_a_broadcast_needs_a_signed_in_user() {
+    let server = TestServer::start().await;
+
+    let refused = server.client().websocket("/broadcast/init").await;
+
+    assert_eq!(refused.err(), Some(StatusCode::UNAUTHORIZED));
+}
+
+#[tokio::test]
+async fn controller_commands_fan_out_to_every_viewer() {
+    let server = TestServer::start().await;
+    let controller_client = server.client();
+    controller_client.sign_up().await;
+
+    let (mut controller, id) = controller_client.start_broadcast().await;
+    let mut first = server.client().join_broadcast(&id.to_string()).await;
+    let mut second = server.client().join_broadcast(&id.to_string()).await;
+    server.wait_for_viewers(&id, 2).await;
+
+    controller.send("scroll:speed_3").await;
+
+    assert_eq!(first.recv().await, "scroll:speed_3");
+    assert_eq!(second.recv().await, "scroll:speed_3");
+    // The controller hears its own commands, which is how it knows they went out
+    assert_eq!(controller.recv().await, "scroll:speed_3");
+}
+
+#[tokio::test]
+async fn unknown_commands_are_reported_to_everyone() {
+    let server = TestServer::start().await;
+    let controller_client = server.client();
+    controller_client.sign_up().await;
+
+    let (mut controller, id) = controller_client.start_broadcast().await;
+    let mut viewer = server.client().join_broadcast(&id.to_string()).await;
+    server.wait_for_viewers(&id, 1).await;
+
+    controller.send("rewind").await;
+
+    assert_eq!(viewer.recv().await, "Invalid message");
+    assert_eq!(controller.recv().await, "Invalid message");
+
+    controller.send("position:abc").await;
+
+    assert_eq!(viewer.recv().await, "Invalid message");
+    assert_eq!(controller.recv().await, "Invalid message");
+
+    controller.send("script: ").await;
+
+    assert_eq!(viewer.recv().await, "Invalid message");
+    assert_eq!(controller.recv().await, "Invalid message");
+}
+
+#[tokio::test]
+async fn late_viewers_are_caught_up_on_the_show() {
+    let server = TestServer::start().await;
+    let controller_client = server.client();
+    controller_client.sign_up().await;
+    let (mut controller, id) = controller_client.start_broadcast().await;
+
+    for command in ["scroll:speed_3", "position:120", "timing:wrap", "scroll"] {
+        controller.send(command).await;
+        assert_eq!(controller.recv().await, command);
+    }
+
+    let mut viewer = server.client().join_broadcast(&id.to_string()).await;
+
+    assert_eq!(viewer.recv().await, "scroll:speed_3");
+    assert_eq!(viewer.recv().await, "position:120");
+    assert_eq!(viewer.recv().await, "timing:wrap");
+    assert_eq!(viewer.recv().await, "scroll");
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_broadcast_is_refused() {
+    let server = TestServer::start().await;
+
+    let mut viewer = server.client().join_broadcast("not-a-broadcast").await;
+
+    assert_eq!(viewer.recv().await, "Broadcast doesn't exist!");
+    assert!(viewer.closed().await);
+}
+
+#[tokio::test]
+async fn viewers_beyond_the_limit_are_turned_away() {
+    let server = TestServer::start_with(Config {
+        max_viewers: 1,
+        ..config()
+    })
+    .await;
+    let controller_client = server.client();
+    controller_client.sign_up().await;
+
+    let (_controller, id) = controller_client.start_broadcast().await;
+    let _viewer = server.client().join_broadcast(&id.to_string()).await;
+    server.wait_for_viewers(&id, 1).await;
+
+    let mut late = server.client().join_broadcast(&id.to_string()).await;
+
+    assert_eq!(late.recv().await, "Broadcast is full!");
+    assert_eq!(server.state.viewers(&id).await, Some(1));
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{config, TestServer, PASSWORD};
+use livescript::Config;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+const DASHBOARD: &str = "https://dashboard.example.com";
+
+fn with_dashboard() -> Config {
+    Config {
+        cors_origins: vec![DASHBOARD.to_string()],
+        ..config()
+    }
+}
+
+#[tokio::test]
+async fn cookie_authenticated_changes_need_the_csrf_token() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    client.sign_up().await;
+    let lat = client.cookie("lat").unwrap();
+    let csrf = client.cookie("csrf").expect("CSRF cookie set at sign up");
+
+    // A forged form post carries the cookies but can't read the token
+    let forged = Client::new()
+        .post(client.url("/auth/totp/enroll"))
+        .header("cookie", format!("lat={lat}; csrf={csrf}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(forged.status(), StatusCode::FORBIDDEN);
+    let body: Value = forged.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_csrf_token");
+
+    let wrong = Client::new()
+        .post(client.url("/auth/totp/enroll"))
+        .header("cookie", format!("lat={lat}; csrf={csrf}"))
+        .header("x-csrf-token", "not-the-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wrong.status(), StatusCode::FORBIDDEN);
+
+    let response = Client::new()
+        .post(client.url("/auth/totp/enroll"))
+        .header("cookie", format!("lat={lat}; csrf={csrf}"))
+        .header("x-csrf-token", &csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Reads don't change anything, so they don't need it
+    assert_eq!(client.get("/me").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn pages_on_other_origins_get_the_csrf_token_from_responses() {
+    let server = TestServer::start_with(with_dashboard()).await;
+    let email = server.client().sign_up().await;
+
+    // Like a browser on the dashboard: cookies are sent, but script can't read the API's
+    let page = Client::builder().cookie_store(true).build().unwrap();
+    let url = |path: &str| server.client().url(path);
+    let login = page
+        .post(url("/auth/login"))
+        .header("origin", DASHBOARD)
+        .json(&json!({ "email": email, "password": PASSWORD }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(login.status(), StatusCode::OK);
+    assert_eq!(
+        login.headers()["access-control-expose-headers"],
+        "x-csrf-token"
+    );
+    let csrf = login.headers()["x-csrf-token"]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let refused = page
+        .post(url("/auth/totp/enroll"))
+        .header("origin", DASHBOARD)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+    let enroll = page
+        .post(url("/auth/totp/enroll"))
+        .header("origin", DASHBOARD)
+        .header("x-csrf-token", &csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(enroll.status(), StatusCode::OK);
+
+    // After a reload the page asks again
+    let refresh = page
+        .post(url("/auth/refresh"))
+        .header("origin", DASHBOARD)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refresh.status(), StatusCode::OK);
+    assert_eq!(refresh.headers()["x-csrf-token"], csrf.as_str());
+}
+
+#[tokio::test]
+async fn bearer_tokens_are_not_subject_to_csrf() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    client.sign_up().await;
+
+    let response = Client::new()
+        .post(client.url("/auth/totp/enroll"))
+        .bearer_auth(client.cookie("lat").unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn allowed_origins_may_call_with_credentials() {
+    let server = TestServer::start_with(with_dashboard()).await;
+    let url = server.client().url("/me");
+
+    let preflight = Client::new()
+        .request(reqwest::Method::OPTIONS, url.clone())
+        .header("origin", DASHBOARD)
+        .header("access-control-request-method", "PATCH")
+        .header(
+            "access-control-request-headers",
+            "content-type, x-csrf-token",
+        )
+        .send()
+        .await
+        .unwrap();
+    let headers = preflight.headers();
+    assert_eq!(headers["access-control-allow-origin"], DASHBOARD);
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+
+    let elsewhere = Client::new()
+        .get(url)
+        .header("origin", "https://evil.example.com")
+        .send()
+        .await
+        .unwrap();
+    assert!(!elsewhere
+        .headers()
+        .contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn websockets_are_refused_to_other_origins() {
+    let server = TestServer::start_with(with_dashboard()).await;
+    let client = server.client();
+    client.sign_up().await;
+
+    let refused = client
+        .websocket_from("/broadcast/init", Some("https://evil.example.com"))
+        .await;
+    assert_eq!(refused.err(), Some(StatusCode::FORBIDDEN));
+
+    let viewer = client
+        .websocket_from("/broadcast/subscribe", Some("https://evil.example.com"))
+        .await;
+    assert_eq!(viewer.err(), Some(StatusCode::FORBIDDEN));
+
+    // The dashboard, the API's own pages, and non-browser clients are all fine
+    assert!(client
+        .websocket_from("/broadcast/init", Some(DASHBOARD))
+        .await
+        .is_ok());
+    let own = format!("http://{}", server.addr);
+    assert!(client
+        .websocket_from("/broadcast/init", Some(&own))
+        .await
+        .is_ok());
+    assert!(client.websocket("/broadcast/init").await.is_ok());
+}
@@ -0,0 +1,311 @@
+//! Boots the real router on an ephemeral port against the in-memory store, with clients that
+//! talk to it the way a browser would: cookies for HTTP, and the same cookies on WebSockets.
+
+// Each test binary pulls this in and only uses part of it
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use livescript::{ApplicationState, Config, LogFormat, TokenConfig};
+use reqwest::{cookie::CookieStore, cookie::Jar, Method, Response, StatusCode, Url};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use uuid::Uuid;
+
+pub const PASSWORD: &str = "Battery!Staple9";
+pub const METRICS_TOKEN: &str = "test-metrics-token";
+
+/// How long to wait for anything the server is expected to send.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn config() -> Config {
+    Config {
+        bind_address: SocketAddr::from(([127, 0, 0, 1], 0)),
+        database_url: "memory:".to_string(),
+        bus_url: "local:".to_string(),
+        auto_migrate: true,
+        tokens: TokenConfig {
+            access_secret: "test-access-secret-that-is-long-enough".to_string(),
+            refresh_secret: "test-refresh-secret-that-is-long-enough".to_string(),
+            access_ttl: Duration::from_secs(60 * 60),
+            refresh_ttl: Duration::from_secs(24 * 60 * 60),
+        },
+        secure_cookies: false,
+        trust_forwarded_proto: false,
+        tls: None,
+        cors_origins: Vec::new(),
+        max_viewers: 10,
+        drain_period: Duration::from_secs(1),
+        instance_id: "test-instance".to_string(),
+        controller_lease: Duration::from_secs(30),
+        log_format: LogFormat::Text,
+        metrics_token: Some(METRICS_TOKEN.to_string()),
+        oidc: None,
+    }
+}
+
+/// A config backed by a fresh SQLite file, so a second server can share or take over its data.
+pub fn file_backed_config() -> (Config, PathBuf) {
+    let database = std::env::temp_dir().join(format!("livescript-{}.db", Uuid::new_v4()));
+    let config = Config {
+        database_url: format!("sqlite:{}", database.display()),
+        ..config()
+    };
+    (config, database)
+}
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub state: Arc<ApplicationState>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with(config()).await
+    }
+
+    pub async fn start_with(config: Config) -> Self {
+        let listener = tokio::net::TcpListener::bind(config.bind_address)
+            .await
+            .expect("bind test listener");
+        let addr = listener.local_addr().expect("test listener address");
+
+        let state = ApplicationState::init(config)
+            .await
+            .expect("initialize application state");
+        let app = livescript::router(state.clone());
+
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .expect("test server");
+        });
+
+        Self { addr, state }
+    }
+
+    fn base(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).expect("server URL")
+    }
+
+    pub fn client(&self) -> TestClient {
+        let jar = Arc::new(Jar::default());
+        let http = reqwest::Client::builder()
+            .cookie_provider(jar.clone())
+            .build()
+            .expect("build HTTP client");
+
+        TestClient {
+            base: self.base(),
+            http,
+            jar,
+        }
+    }
+
+    /// Waits until the broadcast has `count` viewers, since subscribing isn't acknowledged.
+    pub async fn wait_for_viewers(&self, broadcast: &Uuid, count: usize) {
+        timeout(TIMEOUT, async {
+            while self.state.viewers(broadcast).await != Some(count) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("broadcast never reached {count} viewers"));
+    }
+}
+
+/// An HTTP client with its own cookie jar, so each one is a separate signed-in browser.
+pub struct TestClient {
+    base: Url,
+    http: reqwest::Client,
+    jar: Arc<Jar>,
+}
+
+impl TestClient {
+    /// The same browser, cookies and all, talking to another server.
+    pub fn pointed_at(&self, server: &TestServer) -> TestClient {
+        TestClient {
+            base: server.base(),
+            http: self.http.clone(),
+            jar: self.jar.clone(),
+        }
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        self.base.join(path).expect("request URL")
+    }
+
+    pub async fn get(&self, path: &str) -> Response {
+        self.http.get(self.url(path)).send().await.expect("GET")
+    }
+
+    /// Echoes the CSRF cookie like the web client does, once there is one.
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> Response {
+        let mut request = self.http.request(method, self.url(path));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        if let Some(csrf) = self.cookie("csrf") {
+            request = request.header("x-csrf-token", csrf);
+        }
+        request.send().await.expect("HTTP request")
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> Response {
+        self.send(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str, body: Value) -> Response {
+        self.send(Method::PUT, path, Some(body)).await
+    }
+
+    pub async fn patch(&self, path: &str, body: Value) -> Response {
+        self.send(Method::PATCH, path, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Response {
+        self.send(Method::DELETE, path, None).await
+    }
+
+    pub async fn delete_with(&self, path: &str, body: Value) -> Response {
+        self.send(Method::DELETE, path, Some(body)).await
+    }
+
+    pub async fn register(&self, email: &str) -> Response {
+        self.post(
+            "/auth/register",
+            json!({ "email": email, "password": PASSWORD }),
+        )
+        .await
+    }
+
+    pub async fn login(&self, email: &str, password: &str) -> Response {
+        self.post(
+            "/auth/login",
+            json!({ "email": email, "password": password }),
+        )
+        .await
+    }
+
+    /// Registers a fresh account and leaves this client signed in as it.
+    pub async fn sign_up(&self) -> String {
+        let email = format!("user{}@example.com", Uuid::new_v4().simple());
+        let response = self.register(&email).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        email
+    }
+
+    /// The value of a cookie the server has set on this client, if any.
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        let header = self.jar.cookies(&self.base)?;
+        header
+            .to_str()
+            .ok()?
+            .split("; ")
+            .find_map(|pair| pair.strip_prefix(&format!("{name}=")).map(str::to_string))
+    }
+
+    /// Opens a WebSocket carrying this client's cookies, or returns the status it was refused with.
+    pub async fn websocket(&self, path: &str) -> Result<WsClient, StatusCode> {
+        self.websocket_from(path, None).await
+    }
+
+    /// Opens a WebSocket the way a page on `origin` would.
+    pub async fn websocket_from(
+        &self,
+        path: &str,
+        origin: Option<&str>,
+    ) -> Result<WsClient, StatusCode> {
+        let mut url = self.url(path);
+        url.set_scheme("ws").expect("ws scheme");
+
+        let mut request = url.as_str().into_client_request().expect("ws request");
+        if let Some(cookies) = self.jar.cookies(&self.base) {
+            request.headers_mut().insert("cookie", cookies);
+        }
+        if let Some(origin) = origin {
+            request
+                .headers_mut()
+                .insert("origin", origin.parse().expect("origin header"));
+        }
+
+        match tokio_tungstenite::connect_async(request).await {
+            Ok((stream, _)) => Ok(WsClient { stream }),
+            Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+                Err(StatusCode::from_u16(response.status().as_u16()).expect("status code"))
+            }
+            Err(err) => panic!("WebSocket connection failed: {err}"),
+        }
+    }
+
+    /// Starts a broadcast as this client, returning the controller socket and the broadcast id.
+    pub async fn start_broadcast(&self) -> (WsClient, Uuid) {
+        let mut controller = self
+            .websocket("/broadcast/init")
+            .await
+            .expect("start broadcast");
+
+        let greeting = controller.recv().await;
+        let id = greeting
+            .strip_prefix("Broadcast ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .unwrap_or_else(|| panic!("unexpected greeting `{greeting}`"));
+
+        (controller, id)
+    }
+
+    /// Joins a broadcast as a viewer. Viewers don't need to be signed in.
+    pub async fn join_broadcast(&self, id: &str) -> WsClient {
+        let mut viewer = self
+            .websocket("/broadcast/subscribe")
+            .await
+            .expect("subscribe to broadcast");
+        viewer.send(id).await;
+        viewer
+    }
+}
+
+pub struct WsClient {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl WsClient {
+    pub async fn send(&mut self, text: &str) {
+        self.stream
+            .send(Message::Text(text.to_string()))
+            .await
+            .expect("send WebSocket message");
+    }
+
+    /// The next text message, failing the test if none arrives in time.
+    pub async fn recv(&mut self) -> String {
+        loop {
+            let message = timeout(TIMEOUT, self.stream.next())
+                .await
+                .expect("timed out waiting for a WebSocket message")
+                .expect("WebSocket closed")
+                .expect("WebSocket error");
+
+            if let Message::Text(text) = message {
+                return text;
+            }
+        }
+    }
+
+    /// Whether the server closes the socket (or goes quiet) without sending anything else.
+    pub async fn closed(&mut self) -> bool {
+        match timeout(Duration::from_millis(200), self.stream.next()).await {
+            Ok(None | Some(Err(_)) | Some(Ok(Message::Close(_)))) | Err(_) => true,
+            Ok(Some(Ok(_))) => false,
+        }
+    }
+}
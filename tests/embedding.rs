@@ -0,0 +1,73 @@
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{http::HeaderValue, response::Response, Router};
+use common::{config, PASSWORD};
+use reqwest::StatusCode;
+use serde_json::json;
+use tokio::sync::oneshot;
+
+#[tokio::test]
+async fn app_can_be_nested_under_a_prefix_with_extra_layers() {
+    let livescript = livescript::app(config()).await.unwrap();
+    let host = Router::new()
+        .nest("/livescript", livescript)
+        .layer(axum::middleware::map_response(
+            |mut response: Response| async {
+                response
+                    .headers_mut()
+                    .insert("x-host", HeaderValue::from_static("embedded"));
+                response
+            },
+        ));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            host.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+
+    let client = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("http://{addr}/livescript/auth/register"))
+        .json(&json!({ "email": "host@example.com", "password": PASSWORD }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["x-host"], "embedded");
+
+    // Token cookies aren't tied to LiveScript's own paths, so they work under the prefix
+    let response = client
+        .get(format!("http://{addr}/livescript/me"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn serve_returns_once_shutdown_resolves() {
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(livescript::serve(config(), async {
+        let _ = stopped.await;
+    }));
+
+    stop.send(()).unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server didn't shut down")
+        .unwrap();
+    assert!(result.is_ok());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::{file_backed_config, TestServer};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn healthz_answers_while_the_process_is_up() {
+    let server = TestServer::start().await;
+
+    let response = server.client().get("/healthz").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body, json!({ "status": "ok" }));
+}
+
+#[tokio::test]
+async fn readyz_checks_the_database_and_migrations() {
+    let (config, database) = file_backed_config();
+    let server = TestServer::start_with(config).await;
+
+    let response = server.client().get("/readyz").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], true);
+    let checks: Vec<&str> = body["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|check| check["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(checks, ["database", "migrations", "draining"]);
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn readyz_fails_once_draining_starts() {
+    let server = TestServer::start().await;
+
+    server.state.drain().await;
+    let response = server.client().get("/readyz").await;
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    assert_eq!(
+        body["checks"][2],
+        json!({ "name": "draining", "ok": false, "detail": "shutting down" })
+    );
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{config, TestServer, METRICS_TOKEN, PASSWORD};
+use livescript::Config;
+use reqwest::StatusCode;
+use serde_json::json;
+
+async fn scrape(server: &TestServer, token: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new().get(server.client().url("/metrics"));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.expect("GET /metrics")
+}
+
+#[tokio::test]
+async fn metrics_need_the_scrape_token() {
+    let server = TestServer::start().await;
+
+    assert_eq!(
+        scrape(&server, None).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        scrape(&server, Some("not-the-token")).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        scrape(&server, Some(METRICS_TOKEN)).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn metrics_are_off_without_a_token_configured() {
+    let server = TestServer::start_with(Config {
+        metrics_token: None,
+        ..config()
+    })
+    .await;
+
+    assert_eq!(
+        scrape(&server, Some(METRICS_TOKEN)).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn metrics_count_broadcasts_commands_and_logins() {
+    let server = TestServer::start().await;
+    let owner = server.client();
+    let email = owner.sign_up().await;
+    assert_eq!(owner.login(&email, PASSWORD).await.status(), StatusCode::OK);
+    assert_eq!(
+        owner.login(&email, "Wrong!Password9").await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        owner.post("/auth/refresh", json!({})).await.status(),
+        StatusCode::OK
+    );
+
+    let (mut controller, id) = owner.start_broadcast().await;
+    let _viewer = server.client().join_broadcast(&id.to_string()).await;
+    server.wait_for_viewers(&id, 1).await;
+    for command in ["scroll", "position:40", "position:80", "rewind"] {
+        controller.send(command).await;
+        controller.recv().await;
+    }
+
+    let body = scrape(&server, Some(METRICS_TOKEN))
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    for line in [
+        "livescript_live_broadcasts 1".to_string(),
+        format!("livescript_broadcast_subscribers{{broadcast=\"{id}\"}} 1"),
+        "livescript_commands_relayed_total{command=\"scroll\"} 1".to_string(),
+        "livescript_commands_relayed_total{command=\"position\"} 2".to_string(),
+        "livescript_commands_relayed_total{command=\"invalid\"} 1".to_string(),
+        "livescript_logins_total{method=\"password\",outcome=\"success\"} 1".to_string(),
+        "livescript_logins_total{method=\"password\",outcome=\"failure\"} 1".to_string(),
+        "livescript_db_query_duration_seconds_count{operation=\"insert_auth\"} 1".to_string(),
+        "livescript_db_query_duration_seconds_count{operation=\"insert_session\"} 2".to_string(),
+        "livescript_db_query_duration_seconds_count{operation=\"touch_session\"} 1".to_string(),
+    ] {
+        assert!(
+            body.lines().any(|l| l == line),
+            "missing `{line}` in:\n{body}"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{Query, State},
+    response::Redirect,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use common::{config, TestServer};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use livescript::{Config, OidcConfig};
+use reqwest::{redirect::Policy, Client, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use totp_rs::{Secret, TOTP};
+
+const CLIENT_ID: &str = "livescript";
+const CODE: &str = "the-authorization-code";
+const SIGNING_KEY: &[u8] = include_bytes!("fixtures/oidc-key.pem");
+const JWKS: &str = include_str!("fixtures/oidc-jwks.json");
+
+/// What the mock identity provider remembers between requests.
+struct Login {
+    nonce: Option<String>,
+    challenge: Option<String>,
+    discoveries: usize,
+    algorithm: Algorithm,
+}
+
+/// An identity provider that approves whoever asks, as `ada@example.com`.
+#[derive(Clone)]
+struct Issuer {
+    url: String,
+    /// Where to send the browser back to, once the server is up.
+    app: Arc<Mutex<String>>,
+    login: Arc<Mutex<Login>>,
+}
+
+impl Issuer {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = Issuer {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            app: Arc::default(),
+            login: Arc::new(Mutex::new(Login {
+                nonce: None,
+                challenge: None,
+                discoveries: 0,
+                algorithm: Algorithm::RS256,
+            })),
+        };
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(|| async { JWKS }))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .with_state(issuer.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        issuer
+    }
+
+    /// Starts the server with single sign-on through this issuer.
+    async fn server(&self) -> TestServer {
+        let config = Config {
+            oidc: Some(OidcConfig {
+                issuer: self.url.clone(),
+                client_id: CLIENT_ID.to_string(),
+                client_secret: Some("client-secret".to_string()),
+                redirect_url: "https://app.example.com/auth/oidc/callback".to_string(),
+            }),
+            ..config()
+        };
+        let server = TestServer::start_with(config).await;
+        *self.app.lock().unwrap() = format!("http://{}", server.addr);
+        server
+    }
+
+    fn discoveries(&self) -> usize {
+        self.login.lock().unwrap().discoveries
+    }
+}
+
+async fn discovery(State(issuer): State<Issuer>) -> Json<Value> {
+    issuer.login.lock().unwrap().discoveries += 1;
+    let url = &issuer.url;
+    Json(json!({
+        "issuer": url,
+        "authorization_endpoint": format!("{url}/authorize"),
+        "token_endpoint": format!("{url}/token"),
+        "jwks_uri": format!("{url}/jwks"),
+    }))
+}
+
+async fn authorize(
+    State(issuer): State<Issuer>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Redirect {
+    let mut login = issuer.login.lock().unwrap();
+    login.nonce = params.get("nonce").cloned();
+    login.challenge = params.get("code_challenge").cloned();
+
+    let app = issuer.app.lock().unwrap();
+    Redirect::to(&format!(
+        "{app}/auth/oidc/callback?code={CODE}&state={}",
+        params["state"]
+    ))
+}
+
+async fn token(
+    State(issuer): State<Issuer>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let login = issuer.login.lock().unwrap();
+    let verifier = URL_SAFE_NO_PAD.encode(Sha256::digest(form["code_verifier"].as_bytes()));
+    if form["code"] != CODE || login.challenge.as_deref() != Some(verifier.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = json!({
+        "iss": issuer.url,
+        "aud": CLIENT_ID,
+        "sub": "provider-user-1",
+        "email": "ada@example.com",
+        "email_verified": true,
+        "nonce": login.nonce,
+        "iat": now,
+        "exp": now + 300,
+    });
+    let mut header = Header::new(login.algorithm);
+    header.kid = Some("test-key".to_string());
+    let key = match login.algorithm {
+        Algorithm::RS256 => EncodingKey::from_rsa_pem(SIGNING_KEY).unwrap(),
+        _ => EncodingKey::from_secret(JWKS.as_bytes()),
+    };
+
+    Ok(Json(
+        json!({ "id_token": encode(&header, &claims, &key).unwrap() }),
+    ))
+}
+
+/// A browser that doesn't follow redirects, so each step of the flow can be taken by hand.
+fn browser() -> Client {
+    Client::builder()
+        .cookie_store(true)
+        .redirect(Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// Takes `browser` through the provider, returning the callback URL it would be sent to.
+async fn callback_url(browser: &Client, server: &TestServer) -> String {
+    let login = browser
+        .get(format!("http://{}/auth/oidc/login", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(login.status(), StatusCode::SEE_OTHER);
+    let authorize = browser
+        .get(login.headers()["location"].to_str().unwrap())
+        .send()
+        .await
+        .unwrap();
+    authorize.headers()["location"]
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+async fn signing_in_through_the_provider_creates_the_account() {
+    let issuer = Issuer::start().await;
+    let server = issuer.server().await;
+
+    for _ in 0..2 {
+        let client = server.client();
+        let response = client.get("/auth/oidc/login").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let me: Value = client.get("/me").await.json().await.unwrap();
+        assert_eq!(me["profile"]["email"], "ada@example.com");
+    }
+
+    // The second login reused the provider's metadata
+    assert_eq!(issuer.discoveries(), 1);
+}
+
+#[tokio::test]
+async fn two_factor_accounts_still_need_a_code() {
+    let issuer = Issuer::start().await;
+    let server = issuer.server().await;
+
+    let enrolled = server.client();
+    let response = enrolled.get("/auth/oidc/login").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let enrollment: Value = enrolled
+        .post("/auth/totp/enroll", json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let secret = Secret::Encoded(enrollment["secret"].as_str().unwrap().to_string())
+        .to_bytes()
+        .unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let totp = TOTP::new(
+        totp_rs::Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        None,
+        String::new(),
+    )
+    .unwrap();
+    let response = enrolled
+        .post("/auth/totp/confirm", json!({ "code": totp.generate(now) }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The provider vouching for the account only gets as far as the challenge
+    let client = server.client();
+    let response = client.get("/auth/oidc/login").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["totp_required"], true);
+    assert!(client.cookie("lat").is_none());
+
+    // The code after the one that confirmed enrollment, which can't be used twice
+    let response = client
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": body["mfa_token"], "code": totp.generate(now + 30) }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(client.cookie("lat").is_some());
+}
+
+#[tokio::test]
+async fn callbacks_only_complete_in_the_browser_that_started_them() {
+    let issuer = Issuer::start().await;
+    let server = issuer.server().await;
+    let victim = browser();
+    let callback = callback_url(&victim, &server).await;
+
+    // Another browser can't finish it, whether injected into or replayed from
+    let elsewhere = browser().get(&callback).send().await.unwrap();
+    assert_eq!(elsewhere.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = elsewhere.json().await.unwrap();
+    assert_eq!(body["code"], "session_expired");
+
+    let response = victim.get(&callback).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Nor can the same callback be used twice
+    let replayed = victim.get(&callback).send().await.unwrap();
+    assert_eq!(replayed.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn id_tokens_signed_with_other_algorithms_are_refused() {
+    let issuer = Issuer::start().await;
+    let server = issuer.server().await;
+    issuer.login.lock().unwrap().algorithm = Algorithm::HS256;
+
+    let browser = browser();
+    let callback = callback_url(&browser, &server).await;
+    let response = browser.get(&callback).send().await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_token");
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::{config, TestServer, PASSWORD};
+use livescript::{admin, Config};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::task::JoinSet;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+/// Runs a command as `livescript-admin` would, returning what it printed.
+async fn admin(config: &Config, args: &[&str], input: &str) -> String {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut output = Vec::new();
+
+    admin::run(config, &args, input.as_bytes(), &mut output)
+        .await
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+/// Everything runs in one test, since they'd share the database and race to migrate it.
+#[tokio::test]
+#[ignore = "needs a PostgreSQL server: set LIVESCRIPT_TEST_POSTGRES_URL and pass --ignored"]
+async fn postgres_stores_accounts_sessions_and_broadcasts() {
+    let database_url = std::env::var("LIVESCRIPT_TEST_POSTGRES_URL")
+        .expect("LIVESCRIPT_TEST_POSTGRES_URL must point at a PostgreSQL database");
+    let config = Config {
+        database_url,
+        ..config()
+    };
+    let server = TestServer::start_with(config.clone()).await;
+
+    // Accounts and sessions, including an access token outliving its revoked session
+    let laptop = server.client();
+    let email = laptop.sign_up().await;
+    let phone = server.client();
+    assert_eq!(
+        phone.login(&email.to_uppercase(), PASSWORD).await.status(),
+        StatusCode::OK
+    );
+    let phone_token = phone.cookie("lat").unwrap();
+    let sessions: Value = laptop.get("/auth/sessions").await.json().await.unwrap();
+    let sessions = sessions["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let phone_session = sessions
+        .iter()
+        .find(|session| session["current"] == false)
+        .unwrap()["id"]
+        .as_str()
+        .unwrap();
+    let path = format!("/auth/sessions/{phone_session}");
+    assert_eq!(laptop.delete(&path).await.status(), StatusCode::OK);
+    let response = reqwest::Client::new()
+        .get(phone.url("/me"))
+        .bearer_auth(&phone_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = laptop
+        .patch("/me", json!({ "display_name": "Ada", "font_size": 64 }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Value = laptop.get("/me").await.json().await.unwrap();
+    assert_eq!(me["profile"]["display_name"], "Ada");
+    assert_eq!(me["profile"]["font_size"], 64);
+
+    // Constraint violations come back as client errors, not server errors
+    let race = format!("race{}@example.com", Uuid::new_v4().simple());
+    let mut registrations = JoinSet::new();
+    for _ in 0..4 {
+        let client = server.client();
+        let race = race.clone();
+        registrations.spawn(async move { client.register(&race).await.status() });
+    }
+    let mut created = 0;
+    while let Some(status) = registrations.join_next().await {
+        match status.unwrap() {
+            StatusCode::CREATED => created += 1,
+            status => assert_eq!(status, StatusCode::CONFLICT),
+        }
+    }
+    assert_eq!(created, 1);
+    let response = laptop
+        .put(
+            "/me/email",
+            json!({ "email": race.to_uppercase(), "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Two-factor secrets and recovery codes
+    let enrollment: Value = laptop
+        .post("/auth/totp/enroll", json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let secret = Secret::Encoded(enrollment["secret"].as_str().unwrap().to_string());
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret.to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap();
+    let code = totp.generate_current().unwrap();
+    let response = laptop
+        .post("/auth/totp/confirm", json!({ "code": code }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let tablet = server.client();
+    let challenge: Value = tablet.login(&email, PASSWORD).await.json().await.unwrap();
+    let response = tablet
+        .post(
+            "/auth/login/totp",
+            json!({ "mfa_token": challenge["mfa_token"], "code": code }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Teams, roles and API keys, set up the way operators do
+    let team = admin(&config, &["create-team", "Newsroom"], "").await;
+    let team = team.trim();
+    let editor = format!("editor{}@example.com", Uuid::new_v4().simple());
+    let password = format!("{PASSWORD}\n");
+    admin(
+        &config,
+        &["create-user", &editor, "--team", team, "--role", "admin"],
+        &password,
+    )
+    .await;
+    let client = server.client();
+    assert_eq!(
+        client.login(&editor, PASSWORD).await.status(),
+        StatusCode::OK
+    );
+    let response = client
+        .post(
+            "/api-keys",
+            json!({ "name": "Studio desk", "scopes": ["broadcast:control"] }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    let keys: Value = client.get("/api-keys").await.json().await.unwrap();
+    assert_eq!(keys["api_keys"].as_array().unwrap().len(), 1);
+    let path = format!("/api-keys/{}", created["api_key"]["id"].as_str().unwrap());
+    assert_eq!(client.delete(&path).await.status(), StatusCode::OK);
+
+    // Broadcast state survives in the database for viewers who join later
+    let (mut controller, id) = laptop.start_broadcast().await;
+    for command in ["script:Evening-News.txt", "scroll:speed_4"] {
+        controller.send(command).await;
+        assert_eq!(controller.recv().await, command);
+    }
+    let mut viewer = server.client().join_broadcast(&id.to_string()).await;
+    assert_eq!(viewer.recv().await, "script:Evening-News.txt");
+    assert_eq!(viewer.recv().await, "scroll:speed_4");
+    controller.send("state:end").await;
+
+    let response = laptop
+        .delete_with("/me", json!({ "password": PASSWORD }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        server.client().login(&email, PASSWORD).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
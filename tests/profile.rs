@@ -0,0 +1,170 @@
+mod common;
+
+use common::{TestClient, TestServer, PASSWORD};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const NEW_PASSWORD: &str = "Correct!Horse42";
+
+/// Calls `/me` with a bare access token, the way a device that kept only its token would.
+async fn me_with_token(client: &TestClient, token: &str) -> StatusCode {
+    reqwest::Client::new()
+        .get(client.url("/me"))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn profiles_are_read_and_partly_updated() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let email = client.sign_up().await;
+
+    let me: Value = client.get("/me").await.json().await.unwrap();
+    assert_eq!(
+        me["profile"],
+        json!({
+            "email": email,
+            "display_name": null,
+            "scroll_speed": 3,
+            "font_size": 48,
+            "mirror_mode": "none",
+        })
+    );
+
+    let response = client
+        .patch(
+            "/me",
+            json!({ "display_name": "Ada", "mirror_mode": "both" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: Value = response.json().await.unwrap();
+    assert_eq!(updated["profile"]["display_name"], "Ada");
+    assert_eq!(updated["profile"]["mirror_mode"], "both");
+    assert_eq!(updated["profile"]["scroll_speed"], 3);
+
+    let response = client.patch("/me", json!({ "font_size": 500 })).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "validation_failed");
+
+    let me: Value = client.get("/me").await.json().await.unwrap();
+    assert_eq!(me["profile"], updated["profile"]);
+}
+
+#[tokio::test]
+async fn changing_the_email_needs_the_password() {
+    let server = TestServer::start().await;
+    let taken = server.client().sign_up().await;
+    let client = server.client();
+    let email = client.sign_up().await;
+
+    let response = client
+        .put(
+            "/me/email",
+            json!({ "email": "new@example.com", "password": "Wrong!Password9" }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "incorrect_password");
+
+    let response = client
+        .put("/me/email", json!({ "email": taken, "password": PASSWORD }))
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = client
+        .put(
+            "/me/email",
+            json!({ "email": "new@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let me: Value = client.get("/me").await.json().await.unwrap();
+    assert_eq!(me["profile"]["email"], "new@example.com");
+    let other = server.client();
+    assert_eq!(
+        other.login(&email, PASSWORD).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        other.login("new@example.com", PASSWORD).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn changing_the_password_signs_out_other_devices() {
+    let server = TestServer::start().await;
+    let laptop = server.client();
+    let email = laptop.sign_up().await;
+    let phone = server.client();
+    phone.login(&email, PASSWORD).await;
+    let phone_token = phone.cookie("lat").unwrap();
+
+    let response = laptop
+        .put(
+            "/me/password",
+            json!({ "current_password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The phone's access token hasn't expired, but its session is gone
+    assert_eq!(
+        me_with_token(&phone, &phone_token).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        phone.post("/auth/refresh", json!({})).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(laptop.get("/me").await.status(), StatusCode::OK);
+
+    let other = server.client();
+    assert_eq!(
+        other.login(&email, PASSWORD).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        other.login(&email, NEW_PASSWORD).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn deleting_the_account_needs_the_password() {
+    let server = TestServer::start().await;
+    let laptop = server.client();
+    let email = laptop.sign_up().await;
+    let phone = server.client();
+    phone.login(&email, PASSWORD).await;
+    let phone_token = phone.cookie("lat").unwrap();
+
+    let response = laptop
+        .delete_with("/me", json!({ "password": "Wrong!Password9" }))
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(laptop.get("/me").await.status(), StatusCode::OK);
+
+    let response = laptop
+        .delete_with("/me", json!({ "password": PASSWORD }))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(laptop.cookie("lat").is_none());
+
+    assert_eq!(
+        me_with_token(&phone, &phone_token).await,
+        StatusCode::UNAUTHORIZED
+    );
+    let response = server.client().login(&email, PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["code"], "invalid_credentials");
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{file_backed_config, TestServer};
+use livescript::Config;
+use reqwest::StatusCode;
+
+/// The same deployment as `config`, as a separate instance.
+fn instance(config: &Config, instance_id: &str) -> Config {
+    Config {
+        instance_id: instance_id.to_string(),
+        ..config.clone()
+    }
+}
+
+#[tokio::test]
+async fn viewers_can_join_a_broadcast_hosted_on_another_instance() {
+    let (config, database) = file_backed_config();
+    let first = TestServer::start_with(config.clone()).await;
+    let second = TestServer::start_with(config).await;
+
+    let owner = first.client();
+    owner.sign_up().await;
+    let (mut controller, id) = owner.start_broadcast().await;
+    controller.send("scroll:speed_2").await;
+    assert_eq!(controller.recv().await, "scroll:speed_2");
+
+    // The shared database tells the second instance the broadcast is live, and where it's up to
+    let mut viewer = second.client().join_broadcast(&id.to_string()).await;
+    assert_eq!(viewer.recv().await, "scroll:speed_2");
+
+    // Once it has ended there, the second instance stops taking viewers for it too
+    controller.send("state:end").await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let mut late = second.client().join_broadcast(&id.to_string()).await;
+    assert_eq!(late.recv().await, "Broadcast doesn't exist!");
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn only_one_instance_at_a_time_has_the_controller() {
+    let (config, database) = file_backed_config();
+    let first = TestServer::start_with(instance(&config, "first")).await;
+    let second = TestServer::start_with(instance(&config, "second")).await;
+
+    let owner = first.client();
+    owner.sign_up().await;
+    let (controller, id) = owner.start_broadcast().await;
+
+    // The first instance holds the lease, so the second won't hand the broadcast over
+    let path = format!("/broadcast/init?resume={id}");
+    let elsewhere = owner.pointed_at(&second);
+    assert_eq!(
+        elsewhere.websocket(&path).await.err(),
+        Some(StatusCode::CONFLICT)
+    );
+
+    // Once the controller hangs up, the lease is released for it to resume anywhere
+    drop(controller);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let mut resumed = elsewhere.websocket(&path).await.expect("resume broadcast");
+    assert!(resumed
+        .recv()
+        .await
+        .starts_with(&format!("Broadcast {id} resumed.")));
+    assert_eq!(
+        owner.websocket(&path).await.err(),
+        Some(StatusCode::CONFLICT)
+    );
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn instances_only_restore_broadcasts_nobody_else_controls() {
+    let (config, database) = file_backed_config();
+    let first = TestServer::start_with(instance(&config, "first")).await;
+
+    let owner = first.client();
+    owner.sign_up().await;
+    let (_controller, id) = owner.start_broadcast().await;
+
+    // Starting next to a live controller, a new instance leaves its broadcast alone
+    let second = TestServer::start_with(instance(&config, "second")).await;
+    assert_eq!(second.state.viewers(&id).await, None);
+
+    // Restarting as the instance holding the lease brings it back
+    let restarted = TestServer::start_with(instance(&config, "first")).await;
+    assert_eq!(restarted.state.viewers(&id).await, Some(0));
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+#[ignore = "needs a Redis server: set LIVESCRIPT_TEST_REDIS_URL and pass --ignored"]
+async fn redis_relays_commands_between_instances() {
+    let bus_url = std::env::var("LIVESCRIPT_TEST_REDIS_URL")
+        .expect("LIVESCRIPT_TEST_REDIS_URL must point at a Redis server");
+    let (config, database) = file_backed_config();
+    let config = Config { bus_url, ..config };
+    let first = TestServer::start_with(config.clone()).await;
+    let second = TestServer::start_with(config).await;
+
+    let owner = first.client();
+    owner.sign_up().await;
+    let (mut controller, id) = owner.start_broadcast().await;
+    let mut viewer = second.client().join_broadcast(&id.to_string()).await;
+    second.wait_for_viewers(&id, 1).await;
+
+    controller.send("scroll").await;
+    assert_eq!(viewer.recv().await, "scroll");
+    // The controller hears its own command once, not again on its way back from Redis
+    assert_eq!(controller.recv().await, "scroll");
+    controller.send("timing:wrap").await;
+    assert_eq!(controller.recv().await, "timing:wrap");
+
+    controller.send("state:end").await;
+    assert_eq!(viewer.recv().await, "timing:wrap");
+    assert_eq!(viewer.recv().await, "state:end");
+    assert!(viewer.closed().await);
+
+    let _ = std::fs::remove_file(database);
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use common::{file_backed_config, TestServer};
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn draining_warns_everyone_and_refuses_new_broadcasts() {
+    let server = TestServer::start().await;
+    let controller_client = server.client();
+    controller_client.sign_up().await;
+
+    let (mut controller, id) = controller_client.start_broadcast().await;
+    let mut viewer = server.client().join_broadcast(&id.to_string()).await;
+    server.wait_for_viewers(&id, 1).await;
+
+    let drain = tokio::spawn({
+        let state = server.state.clone();
+        async move { state.drain().await }
+    });
+
+    assert_eq!(viewer.recv().await, "state:restarting retry_after=1");
+    assert_eq!(controller.recv().await, "state:restarting retry_after=1");
+
+    let refused = controller_client.websocket("/broadcast/init").await;
+    assert_eq!(refused.err(), Some(StatusCode::SERVICE_UNAVAILABLE));
+
+    // Everyone leaving as asked lets the drain finish early
+    drop(controller);
+    drop(viewer);
+    drain.await.unwrap();
+}
+
+#[tokio::test]
+async fn ending_a_broadcast_closes_it_for_viewers() {
+    let server = TestServer::start().await;
+    let controller_client = server.client();
+    controller_client.sign_up().await;
+
+    let (mut controller, id) = controller_client.start_broadcast().await;
+    let mut viewer = server.client().join_broadcast(&id.to_string()).await;
+    server.wait_for_viewers(&id, 1).await;
+
+    controller.send("state:end").await;
+
+    assert_eq!(viewer.recv().await, "state:end");
+    assert!(viewer.closed().await);
+    assert_eq!(server.state.viewers(&id).await, None);
+}
+
+#[tokio::test]
+async fn only_the_owner_can_resume_a_broadcast() {
+    let server = TestServer::start().await;
+    let owner = server.client();
+    owner.sign_up().await;
+    let (controller, id) = owner.start_broadcast().await;
+
+    let stranger = server.client();
+    stranger.sign_up().await;
+    let path = format!("/broadcast/init?resume={id}");
+    assert_eq!(
+        stranger.websocket(&path).await.err(),
+        Some(StatusCode::FORBIDDEN)
+    );
+
+    // The owner can't take over while still connected, only once they've dropped
+    assert_eq!(
+        owner.websocket(&path).await.err(),
+        Some(StatusCode::CONFLICT)
+    );
+    drop(controller);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let mut resumed = owner.websocket(&path).await.expect("resume broadcast");
+    assert!(resumed
+        .recv()
+        .await
+        .starts_with(&format!("Broadcast {id} resumed.")));
+
+    let unknown = format!("/broadcast/init?resume={}", Uuid::new_v4());
+    assert_eq!(
+        owner.websocket(&unknown).await.err(),
+        Some(StatusCode::NOT_FOUND)
+    );
+}
+
+#[tokio::test]
+async fn racing_resumes_get_one_controller() {
+    let server = TestServer::start().await;
+    let owner = server.client();
+    owner.sign_up().await;
+    let (controller, id) = owner.start_broadcast().await;
+    drop(controller);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    // Both may pass the check before the upgrade, but only one takes the broadcast over
+    let path = format!("/broadcast/init?resume={id}");
+    let (first, second) = tokio::join!(owner.websocket(&path), owner.websocket(&path));
+    let mut greetings = Vec::new();
+    for attempt in [first, second] {
+        match attempt {
+            Ok(mut socket) => greetings.push(socket.recv().await),
+            Err(status) => assert_eq!(status, StatusCode::CONFLICT),
+        }
+    }
+
+    let resumed = format!("Broadcast {id} resumed.");
+    assert_eq!(
+        greetings
+            .iter()
+            .filter(|greeting| greeting.starts_with(&resumed))
+            .count(),
+        1
+    );
+    for greeting in greetings
+        .iter()
+        .filter(|greeting| !greeting.starts_with(&resumed))
+    {
+        assert_eq!(greeting, "Broadcast already has a controller!");
+    }
+}
+
+#[tokio::test]
+async fn live_broadcasts_survive_a_restart() {
+    let (config, database) = file_backed_config();
+
+    let before = TestServer::start_with(config.clone()).await;
+    let owner = before.client();
+    owner.sign_up().await;
+    let (_controller, id) = owner.start_broadcast().await;
+
+    before.state.drain().await;
+    before.state.persist_broadcasts().await.unwrap();
+
+    let after = TestServer::start_with(config).await;
+    assert_eq!(after.state.viewers(&id).await, Some(0));
+
+    // The same signed-in browser picks the broadcast back up on the new server
+    let owner = owner.pointed_at(&after);
+    let mut controller = owner
+        .websocket(&format!("/broadcast/init?resume={id}"))
+        .await
+        .expect("resume broadcast");
+    assert!(controller
+        .recv()
+        .await
+        .starts_with(&format!("Broadcast {id} resumed.")));
+
+    let mut viewer = after.client().join_broadcast(&id.to_string()).await;
+    after.wait_for_viewers(&id, 1).await;
+    controller.send("scroll").await;
+    assert_eq!(viewer.recv().await, "scroll");
+
+    let _ = std::fs::remove_file(database);
+}
+
+#[tokio::test]
+async fn broadcast_state_survives_a_crash() {
+    let (config, database) = file_backed_config();
+
+    let before = TestServer::start_with(config.clone()).await;
+    let owner = before.client();
+    owner.sign_up().await;
+    let (mut controller, id) = owner.start_broadcast().await;
+
+    // Each command is saved before it goes out, so hearing it back means it's on disk
+    for command in ["script:Evening-News.txt", "scroll:speed_4", "position:300"] {
+        controller.send(command).await;
+        assert_eq!(controller.recv().await, command);
+    }
+
+    // No drain and no final save: the next server only has what was written as it happened
+    let after = TestServer::start_with(config).await;
+    let owner = owner.pointed_at(&after);
+    let mut resumed = owner
+        .websocket(&format!("/broadcast/init?resume={id}"))
+        .await
+        .expect("resume broadcast");
+
+    assert!(resumed
+        .recv()
+        .await
+        .starts_with(&format!("Broadcast {id} resumed.")));
+    assert_eq!(resumed.recv().await, "script:Evening-News.txt");
+    assert_eq!(resumed.recv().await, "scroll:speed_4");
+    assert_eq!(resumed.recv().await, "position:300");
+
+    let mut viewer = after.client().join_broadcast(&id.to_string()).await;
+    assert_eq!(viewer.recv().await, "script:Evening-News.txt");
+    assert_eq!(viewer.recv().await, "scroll:speed_4");
+    assert_eq!(viewer.recv().await, "position:300");
+
+    let _ = std::fs::remove_file(database);
+}
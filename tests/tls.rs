@@ -0,0 +1,163 @@
+mod common;
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::{config, TestServer, PASSWORD};
+use livescript::{Config, TlsConfig};
+use reqwest::{Certificate, Client, StatusCode};
+use serde_json::json;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Copies one of the fixture certificates where a server can be pointed at it and have it
+/// swapped out from under it.
+fn install(pair: &str, dir: &Path) {
+    std::fs::copy(fixture(&format!("cert-{pair}.pem")), dir.join("cert.pem")).unwrap();
+    std::fs::copy(fixture(&format!("key-{pair}.pem")), dir.join("key.pem")).unwrap();
+}
+
+/// A client that only trusts one of the fixture certificates.
+fn trusting(pair: &str) -> Client {
+    let pem = std::fs::read(fixture(&format!("cert-{pair}.pem"))).unwrap();
+    Client::builder()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(Certificate::from_pem(&pem).unwrap())
+        .build()
+        .unwrap()
+}
+
+fn free_port() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn healthz(client: &Client, addr: SocketAddr) -> reqwest::Result<StatusCode> {
+    let url = format!("https://localhost:{}/healthz", addr.port());
+    Ok(client.get(url).send().await?.status())
+}
+
+/// Runs the real server over TLS until the returned sender is dropped.
+async fn serve_tls(dir: &Path) -> (SocketAddr, oneshot::Sender<()>) {
+    let addr = free_port();
+    let config = Config {
+        bind_address: addr,
+        tls: Some(TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+            reload_interval: Duration::from_millis(200),
+        }),
+        ..config()
+    };
+
+    let (stop, stopped) = oneshot::channel::<()>();
+    tokio::spawn(livescript::serve(config, async {
+        let _ = stopped.await;
+    }));
+
+    for _ in 0..50 {
+        if healthz(&trusting("a"), addr).await.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    (addr, stop)
+}
+
+#[tokio::test]
+async fn serves_https_and_picks_up_a_renewed_certificate() {
+    let dir = std::env::temp_dir().join(format!("livescript-tls-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    install("a", &dir);
+
+    let (addr, _stop) = serve_tls(&dir).await;
+    assert_eq!(healthz(&trusting("a"), addr).await.unwrap(), StatusCode::OK);
+    assert!(healthz(&trusting("b"), addr).await.is_err());
+
+    // Renewal tools replace the files in place; new connections should get the new pair
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    install("b", &dir);
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    assert_eq!(healthz(&trusting("b"), addr).await.unwrap(), StatusCode::OK);
+    assert!(healthz(&trusting("a"), addr).await.is_err());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn refuses_to_start_with_a_missing_certificate() {
+    let dir = std::env::temp_dir().join(format!("livescript-tls-{}", Uuid::new_v4()));
+    let config = Config {
+        bind_address: free_port(),
+        tls: Some(TlsConfig {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+            reload_interval: Duration::from_secs(60),
+        }),
+        ..config()
+    };
+
+    assert!(livescript::serve(config, std::future::pending())
+        .await
+        .is_err());
+}
+
+async fn register_cookie(server: &TestServer, forwarded_proto: Option<&str>) -> String {
+    let mut request = Client::new()
+        .post(server.client().url("/auth/register"))
+        .json(&json!({
+            "email": format!("user{}@example.com", Uuid::new_v4().simple()),
+            "password": PASSWORD,
+        }));
+    if let Some(proto) = forwarded_proto {
+        request = request.header("x-forwarded-proto", proto);
+    }
+
+    let response = request.send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .find(|cookie| cookie.starts_with("lat="))
+        .expect("access token cookie")
+}
+
+#[tokio::test]
+async fn cookies_are_secure_when_a_trusted_proxy_terminated_https() {
+    let server = TestServer::start_with(Config {
+        trust_forwarded_proto: true,
+        ..config()
+    })
+    .await;
+
+    assert!(register_cookie(&server, Some("https"))
+        .await
+        .contains("Secure"));
+    assert!(!register_cookie(&server, Some("http"))
+        .await
+        .contains("Secure"));
+    assert!(!register_cookie(&server, None).await.contains("Secure"));
+}
+
+#[tokio::test]
+async fn forwarded_proto_is_ignored_unless_trusted() {
+    let server = TestServer::start().await;
+
+    assert!(!register_cookie(&server, Some("https"))
+        .await
+        .contains("Secure"));
+}